@@ -0,0 +1,91 @@
+use crate::config::Config;
+use axum::async_trait;
+use axum::extract::{FromRequest, RequestParts};
+use axum::http::HeaderMap;
+use std::convert::Infallible;
+use std::sync::Arc;
+
+/// Base that URLs of pastes are built below, the configured base path optionally prefixed with
+/// the scheme and host forwarded by a proxy.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct BaseUrl(pub String);
+
+/// Last entry of the `name` header, which is the one set by the closest proxy.
+fn last_entry<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers
+        .get_all(name)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .map(str::trim)
+        .next_back()
+}
+
+/// Scheme and host from the `X-Forwarded-Proto` and `X-Forwarded-Host` headers. `None` if the
+/// host is missing or contains characters that cannot be part of a host.
+fn origin(headers: &HeaderMap) -> Option<String> {
+    let host = last_entry(headers, "x-forwarded-host").filter(|host| {
+        !host.is_empty()
+            && host
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-' | ':' | '[' | ']'))
+    })?;
+
+    let scheme = match last_entry(headers, "x-forwarded-proto") {
+        Some(proto) if proto.eq_ignore_ascii_case("https") => "https",
+        _ => "http",
+    };
+
+    Some(format!("{scheme}://{host}"))
+}
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for BaseUrl {
+    type Rejection = Infallible;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let (base_path, forwarded_host) = req
+            .extensions()
+            .get::<Arc<Config>>()
+            .map_or((String::new(), false), |config| {
+                (config.base_path.clone(), config.forwarded_host)
+            });
+
+        let origin = forwarded_host
+            .then(|| origin(req.headers()))
+            .flatten()
+            .unwrap_or_default();
+
+        Ok(Self(format!("{origin}{base_path}")))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    #[test]
+    fn forwarded_origin() {
+        let mut headers = HeaderMap::new();
+        assert_eq!(origin(&headers), None);
+
+        headers.insert(
+            "x-forwarded-host",
+            HeaderValue::from_static("bin.example.com"),
+        );
+        assert_eq!(origin(&headers).as_deref(), Some("http://bin.example.com"));
+
+        headers.insert("x-forwarded-proto", HeaderValue::from_static("https"));
+        assert_eq!(origin(&headers).as_deref(), Some("https://bin.example.com"));
+
+        headers.insert(
+            "x-forwarded-host",
+            HeaderValue::from_static("evil.com, [::1]:8088"),
+        );
+        assert_eq!(origin(&headers).as_deref(), Some("https://[::1]:8088"));
+
+        headers.insert("x-forwarded-host", HeaderValue::from_static("a.com/\"x"));
+        assert_eq!(origin(&headers), None);
+    }
+}
@@ -1,28 +1,53 @@
-use crate::db::Database;
-use crate::highlight::highlight;
+use crate::conditional;
+use crate::config::Config;
+use crate::db::{Database, Deletion, Quota};
+use crate::highlight::{self, highlight, Breaker};
 use crate::id::Id;
-use crate::Error;
+use crate::{password, Error};
 use axum::extract::Path;
 use lru::LruCache;
 use std::collections::{HashMap, HashSet};
 use std::hash::Hash;
+use std::net::IpAddr;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::broadcast;
+
+/// Time after which a highlight counts as failed. The blocking task itself is not cancelled.
+const HIGHLIGHT_TIMEOUT: Duration = Duration::from_secs(2);
+
+/// Time for which the number of live pastes is cached to render the index page.
+const QUOTA_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+/// Number of random identifiers tried before an insert fails because all of them were taken.
+const RANDOM_ID_ATTEMPTS: usize = 8;
+
+/// Number of appended chunks buffered for each tail subscriber before it lags behind.
+const TAIL_CAPACITY: usize = 64;
 
 #[derive(Clone, Debug, PartialEq, Eq, Hash)]
 pub struct Key {
     id: Id,
-    ext: String,
+    ext: Option<String>,
+}
+
+/// Formatted paste together with the extension it was highlighted with, so that cache hits need
+/// no extension detection.
+#[derive(Clone)]
+pub struct Formatted {
+    html: String,
+    extension: Option<String>,
 }
 
 pub struct Inner {
-    cache: LruCache<Key, String>,
-    cached: HashMap<Id, HashSet<String>>,
+    cache: LruCache<Key, Formatted>,
+    cached: HashMap<Id, HashSet<Option<String>>>,
 }
 
 type Cache = Arc<Mutex<Inner>>;
 
 impl Key {
-    pub fn new(id: Id, ext: String) -> Key {
+    pub fn new(id: Id, ext: Option<String>) -> Key {
         Self { id, ext }
     }
 
@@ -30,18 +55,38 @@ impl Key {
         self.id.to_string()
     }
 
+    pub fn raw_id(&self) -> Id {
+        self.id
+    }
+
+    /// Extension given in the path, if any.
+    pub fn ext(&self) -> Option<&str> {
+        self.ext.as_deref()
+    }
+
+    /// Extension given in the path or `txt` if there was none.
     pub fn extension(&self) -> String {
-        self.ext.clone()
+        self.ext.clone().unwrap_or_else(|| "txt".to_string())
     }
 }
 
+/// Return `true` if `ext` is a non-empty extension consisting of ASCII letters, digits, `_`, `+`
+/// and `-`.
+pub fn is_valid_extension(ext: &str) -> bool {
+    !ext.is_empty()
+        && ext
+            .bytes()
+            .all(|b| b.is_ascii_alphanumeric() || matches!(b, b'_' | b'+' | b'-'))
+}
+
 impl TryFrom<Path<String>> for Key {
     type Error = Error;
 
     fn try_from(value: Path<String>) -> Result<Self, Self::Error> {
         let (id, ext) = match value.split_once('.') {
-            None => (Id::try_from(value.as_str())?, "txt".to_string()),
-            Some((id, ext)) => (Id::try_from(id)?, ext.to_string()),
+            None => (Id::try_from(value.as_str())?, None),
+            Some((_, ext)) if !is_valid_extension(ext) => return Err(Error::InvalidExtension),
+            Some((id, ext)) => (Id::try_from(id)?, Some(ext.to_string())),
         };
 
         Ok(Self { id, ext })
@@ -58,11 +103,11 @@ impl Inner {
         }
     }
 
-    pub fn get<'a>(&'a mut self, k: &Key) -> Option<&'a String> {
+    pub fn get<'a>(&'a mut self, k: &Key) -> Option<&'a Formatted> {
         self.cache.get(k)
     }
 
-    pub fn put(&mut self, k: Key, v: String) -> Option<String> {
+    pub fn put(&mut self, k: Key, v: Formatted) -> Option<Formatted> {
         if let Some(cached) = self.cached.get_mut(&k.id) {
             if !cached.contains(&k.ext) {
                 cached.insert(k.ext.clone());
@@ -79,7 +124,7 @@ impl Inner {
     pub fn remove(&mut self, id: Id) {
         if let Some(exts) = self.cached.remove(&id) {
             for ext in exts {
-                tracing::debug!("evicting {id:?}.{ext}");
+                tracing::debug!(?id, ?ext, "evicting");
                 self.cache.pop(&Key::new(id, ext));
             }
         }
@@ -91,71 +136,322 @@ impl Inner {
 pub struct Layer {
     db: Database,
     cache: Cache,
+    tails: Arc<Mutex<HashMap<Id, broadcast::Sender<String>>>>,
+    breaker: Arc<Mutex<Breaker>>,
+    live_count: Arc<Mutex<Option<(Instant, usize)>>>,
+    highlight_timeout: Duration,
 }
 
 /// Entry and syntax highlighted text.
 pub struct Entry {
     pub formatted: String,
     pub seconds_since_creation: u32,
+    /// Rendered as plain text because of overly long lines.
+    pub long_lines: bool,
+    /// Original file name.
+    pub filename: Option<String>,
+    /// Extension the paste was highlighted with if it was given or detected.
+    pub extension: Option<String>,
+    /// Clients may store the rendered paste, see [`conditional::cacheable`].
+    pub cacheable: bool,
+    /// Text can still be appended.
+    pub appendable: bool,
+    /// Readable only with a password, so raw text and downloads are not linked.
+    pub protected: bool,
 }
 
 impl Layer {
     pub fn new(db: Database, cache_size: usize) -> Self {
         let cache = Arc::new(Mutex::new(Inner::new(cache_size)));
-        Self { db, cache }
+        let tails = Arc::new(Mutex::new(HashMap::new()));
+        let breaker = Arc::new(Mutex::new(Breaker::default()));
+        let live_count = Arc::new(Mutex::new(None));
+
+        Self {
+            db,
+            cache,
+            tails,
+            breaker,
+            live_count,
+            highlight_timeout: HIGHLIGHT_TIMEOUT,
+        }
     }
 
-    /// Insert `entry` into the database.
-    pub async fn insert(&self, id: Id, entry: crate::Entry) -> Result<(), Error> {
-        self.db.insert(id, entry).await
+    /// Insert `entry` into the database unless that exceeds `quota`.
+    pub async fn insert(&self, id: Id, entry: crate::Entry, quota: Quota) -> Result<(), Error> {
+        self.db.insert(id, entry, quota).await?;
+        *self.live_count.lock().unwrap() = None;
+        Ok(())
     }
 
-    /// Look up or generate HTML formatted data. Return `None` if `key` is not found.
-    pub async fn get_formatted(&self, key: Key) -> Result<Entry, Error> {
-        let entry = self.db.get(key.id).await?;
-        let seconds_since_creation = entry.seconds_since_creation;
+    /// Insert `entry` with a random identifier, retrying with a fresh one if it is already taken.
+    pub async fn insert_random(&self, entry: crate::Entry, quota: Quota) -> Result<Id, Error> {
+        let mut attempts = 1;
 
-        if let Some(cached) = self.cache.lock().unwrap().get(&key) {
+        loop {
+            let id = Id::random().await?;
+
+            match self.insert(id, entry.clone(), quota).await {
+                Err(Error::IdTaken) if attempts < RANDOM_ID_ATTEMPTS => attempts += 1,
+                result => return result.map(|()| id),
+            }
+        }
+    }
+
+    /// Count live pastes and remember the result for [`Layer::at_capacity`].
+    async fn count_live(&self) -> Result<usize, Error> {
+        let count = self.db.count().await?;
+        *self.live_count.lock().unwrap() = Some((Instant::now(), count));
+        Ok(count)
+    }
+
+    /// Return `true` if the configured maximum number of live pastes is reached. The count is only
+    /// refreshed after inserts, deletions or [`QUOTA_CHECK_INTERVAL`].
+    pub async fn at_capacity(&self, config: &Config) -> Result<bool, Error> {
+        let max = match config.max_pastes {
+            Some(max) => max,
+            None => return Ok(false),
+        };
+
+        let cached = *self.live_count.lock().unwrap();
+
+        let count = match cached {
+            Some((checked, count)) if checked.elapsed() < QUOTA_CHECK_INTERVAL => count,
+            _ => self.count_live().await?,
+        };
+
+        Ok(count >= max)
+    }
+
+    /// Make `entry` count towards the quota of `ip` if one is configured and return the limits
+    /// on live pastes that inserting it must not exceed.
+    pub fn claim_quota(entry: &mut crate::Entry, ip: IpAddr, config: &Config) -> Quota {
+        if config.max_pastes_per_ip.is_some() {
+            entry.client_ip = Some(ip);
+        }
+
+        Quota {
+            max_entries: config.max_pastes,
+            max_per_client: config.max_pastes_per_ip,
+        }
+    }
+
+    /// Look up or generate HTML formatted data. Pastes without an extension in `key` are
+    /// highlighted with their stored extension, the one detected from their content or the
+    /// configured default syntax, in that order. Syntaxes that repeatedly fail or time out
+    /// are rendered as plain text until their cooldown has passed, as are pastes with lines longer
+    /// than the configured threshold. Protected pastes are only formatted if `password` matches and
+    /// are never cached, so that their decrypted content is not kept around. Burn after reading
+    /// pastes are deleted only after they were formatted successfully.
+    pub async fn get_formatted(
+        &self,
+        key: Key,
+        password: Option<String>,
+        config: &Config,
+    ) -> Result<Entry, Error> {
+        let mut entry = self.db.peek(key.id).await?;
+        password::unlock(&mut entry, password).await?;
+
+        let protected = entry.ciphertext.is_some();
+        let cacheable = conditional::cacheable(&entry);
+        let appendable = entry.appendable.unwrap_or(false);
+        let seconds_since_creation = entry.seconds_since_creation;
+        let filename = entry.filename.clone();
+        let max_line_length = config.highlight_max_line_length;
+        let long_lines =
+            max_line_length > 0 && entry.text.lines().any(|line| line.len() > max_line_length);
+
+        let cached = self
+            .cache
+            .lock()
+            .unwrap()
+            .get(&key)
+            .filter(|_| !protected)
+            .cloned();
+
+        if let Some(cached) = cached {
             tracing::debug!(?key, "found cached item");
 
             return Ok(Entry {
-                formatted: cached.to_string(),
+                formatted: cached.html,
                 seconds_since_creation,
+                long_lines,
+                filename,
+                extension: cached.extension,
+                cacheable,
+                appendable,
+                protected,
             });
         }
 
+        // An extension in the path or stored on insert always wins over detection.
+        let extension = key
+            .ext
+            .clone()
+            .or_else(|| entry.extension.clone())
+            .or_else(|| highlight::detect_extension(&entry.text));
+
         let burn_after_reading = entry.burn_after_reading.unwrap_or(false);
-        let ext = key.ext.clone();
-        let formatted = tokio::task::spawn_blocking(move || highlight(&entry, &ext)).await??;
+        let ext = extension
+            .clone()
+            .or_else(|| config.default_syntax.clone())
+            .unwrap_or_else(|| "txt".to_string());
+
+        let disabled = !long_lines
+            && self
+                .breaker
+                .lock()
+                .unwrap()
+                .is_disabled(&ext, Instant::now());
+        let plain = long_lines || disabled;
+        let syntax = if plain {
+            "txt".to_string()
+        } else {
+            ext.clone()
+        };
+
+        let result = tokio::time::timeout(
+            self.highlight_timeout,
+            tokio::task::spawn_blocking(move || highlight(&entry, &syntax)),
+        )
+        .await
+        .map_err(|_| Error::HighlightTimeout)
+        .and_then(|result| result?);
+
+        let formatted = match result {
+            Ok(formatted) => {
+                if !plain {
+                    self.breaker.lock().unwrap().record_success(&ext);
+                }
+                formatted
+            }
+            Err(err) => {
+                if !plain {
+                    self.breaker.lock().unwrap().record_failure(
+                        &ext,
+                        config.highlight_failures,
+                        config.highlight_cooldown,
+                        Instant::now(),
+                    );
+                }
+                return Err(err);
+            }
+        };
 
-        if !burn_after_reading {
+        // Burn only once the paste could be formatted, so that failures do not lose it.
+        if burn_after_reading && self.db.delete(key.id).await? == 0 {
+            return Err(Error::NotFound);
+        }
+
+        if !burn_after_reading && !disabled && !protected {
             tracing::debug!(?key, "cache item");
-            self.cache.lock().unwrap().put(key, formatted.clone());
+            let cached = Formatted {
+                html: formatted.clone(),
+                extension: extension.clone(),
+            };
+            self.cache.lock().unwrap().put(key, cached);
         }
 
         Ok(Entry {
             formatted,
             seconds_since_creation,
+            long_lines,
+            filename,
+            extension,
+            cacheable,
+            appendable,
+            protected,
         })
     }
 
-    /// Get raw content for `id` or `None` if not found.
-    pub async fn get(&self, id: Id) -> Result<crate::Entry, Error> {
-        self.db.get(id).await
+    /// Get raw content for `id`, authorized by `password` if the entry is protected.
+    pub async fn get(&self, id: Id, password: Option<String>) -> Result<crate::Entry, Error> {
+        self.db.get(id, password).await
+    }
+
+    /// Get raw content for `id` without consuming burn after reading entries.
+    pub async fn peek(&self, id: Id) -> Result<crate::Entry, Error> {
+        self.db.peek(id).await
+    }
+
+    /// Delete `id` if it is still within the deletion `window` in seconds or `window` is `None`.
+    /// Deleting an entry again succeeds, deleting an unknown entry only if `missing_ok` is set.
+    pub async fn delete(&self, id: Id, missing_ok: bool, window: Option<u64>) -> Result<(), Error> {
+        match self.db.delete_recent(id, window).await? {
+            Deletion::Deleted => {
+                self.cache.lock().unwrap().remove(id);
+                *self.live_count.lock().unwrap() = None;
+                self.tails.lock().unwrap().remove(&id);
+                Ok(())
+            }
+            Deletion::AlreadyDeleted => Ok(()),
+            Deletion::NotFound if missing_ok => Ok(()),
+            Deletion::NotFound => Err(Error::NotFound),
+            Deletion::Expired => Err(Error::DeletionTimeExpired),
+        }
     }
 
-    /// Delete `id`.
-    pub async fn delete(&self, id: Id) -> Result<(), Error> {
+    /// Append `text` to `id` if `token` matches and `check` accepts the combined text. Cached
+    /// highlights are evicted so that they are regenerated on the next view.
+    pub async fn append<F>(
+        &self,
+        id: Id,
+        token: String,
+        text: String,
+        check: F,
+    ) -> Result<usize, Error>
+    where
+        F: FnOnce(&str) -> Result<(), Error> + Send + 'static,
+    {
+        let size = self.db.append(id, token, text.clone(), check).await?;
         self.cache.lock().unwrap().remove(id);
-        self.db.delete(id).await
+
+        let mut tails = self.tails.lock().unwrap();
+
+        if let Some(sender) = tails.get(&id) {
+            if sender.send(text).is_err() {
+                tails.remove(&id);
+            }
+        }
+
+        Ok(size)
+    }
+
+    /// Subscribe to text appended to `id`, which must have been created as appendable and must not
+    /// be password protected.
+    pub async fn subscribe(&self, id: Id) -> Result<broadcast::Receiver<String>, Error> {
+        let entry = self.db.peek(id).await?;
+
+        if !entry.appendable.unwrap_or(false) {
+            return Err(Error::NotFound);
+        }
+
+        if entry.ciphertext.is_some() {
+            return Err(Error::Unauthorized);
+        }
+
+        Ok(self
+            .tails
+            .lock()
+            .unwrap()
+            .entry(id)
+            .or_insert_with(|| broadcast::channel(TAIL_CAPACITY).0)
+            .subscribe())
     }
 
-    /// Purge expired items from database and cache.
+    /// Purge expired items from database and cache as well as tails without subscribers.
     pub async fn purge(&self) -> Result<(), Error> {
         for id in self.db.purge().await? {
             tracing::debug!(?id, "remove from cache");
             self.cache.lock().unwrap().remove(id);
+            self.tails.lock().unwrap().remove(&id);
         }
+
+        self.tails
+            .lock()
+            .unwrap()
+            .retain(|_, sender| sender.receiver_count() > 0);
+
         Ok(())
     }
 }
@@ -187,14 +483,208 @@ mod tests {
         };
 
         let id = Id::from(1234);
-        let key = Key::new(id, "rs".to_string());
-        layer.insert(id, entry).await?;
-        assert!(layer.get_formatted(key.clone()).await.is_ok());
+        let key = Key::new(id, Some("rs".to_string()));
+        let config = Config::default();
+        layer.insert(id, entry, Quota::default()).await?;
+        assert!(layer
+            .get_formatted(key.clone(), None, &config)
+            .await
+            .is_ok());
 
         tokio::time::sleep(std::time::Duration::from_millis(2000)).await;
         layer.purge().await?;
-        assert!(layer.db.get(id).await.is_err());
-        assert!(layer.get_formatted(key).await.is_err());
+        assert!(layer.db.get(id, None).await.is_err());
+        assert!(layer.get_formatted(key, None, &config).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn unsubscribed_tail_is_purged() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(db::Open::Memory)?;
+        let layer = Layer::new(db, 128);
+
+        let entry = crate::Entry {
+            text: "hello".to_string(),
+            append_token: Some("token".to_string()),
+            ..Default::default()
+        };
+
+        let id = Id::from(1234);
+        layer.insert(id, entry, Quota::default()).await?;
+
+        let receiver = layer.subscribe(id).await?;
+        layer.purge().await?;
+        assert!(layer.tails.lock().unwrap().contains_key(&id));
+
+        drop(receiver);
+        layer.purge().await?;
+        assert!(layer.tails.lock().unwrap().is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn burn_survives_highlight_failure() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(db::Open::Memory)?;
+        let mut layer = Layer::new(db, 128);
+        layer.highlight_timeout = Duration::ZERO;
+
+        let entry = crate::Entry {
+            text: "fn main() {}\n".repeat(1000),
+            burn_after_reading: Some(true),
+            ..Default::default()
+        };
+
+        let id = Id::from(1234);
+        let key = Key::new(id, Some("rs".to_string()));
+        let config = Config::default();
+        layer.insert(id, entry, Quota::default()).await?;
+
+        assert!(matches!(
+            layer.get_formatted(key.clone(), None, &config).await,
+            Err(Error::HighlightTimeout)
+        ));
+        assert!(layer.db.peek(id).await.is_ok());
+
+        layer.highlight_timeout = HIGHLIGHT_TIMEOUT;
+        assert!(layer
+            .get_formatted(key.clone(), None, &config)
+            .await
+            .is_ok());
+        assert!(layer.db.peek(id).await.is_err());
+        assert!(layer.get_formatted(key, None, &config).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn long_line_is_plain_text() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(db::Open::Memory)?;
+        let layer = Layer::new(db, 128);
+
+        let entry = crate::Entry {
+            text: format!("fn main() {{}}\nlet x = \"{}\";\n", "a".repeat(1000)),
+            ..Default::default()
+        };
+
+        let id = Id::from(1234);
+        layer.insert(id, entry, Quota::default()).await?;
+
+        let key = Key::new(id, Some("rs".to_string()));
+
+        let config = Config::default();
+        let formatted = layer.get_formatted(key.clone(), None, &config).await?;
+        assert!(!formatted.long_lines);
+        assert!(formatted.formatted.contains("source rust"));
+
+        let layer = Layer::new(layer.db.clone(), 128);
+
+        let config = Config {
+            highlight_max_line_length: 100,
+            ..Default::default()
+        };
+
+        let formatted = layer.get_formatted(key, None, &config).await?;
+        assert!(formatted.long_lines);
+        assert!(!formatted.formatted.contains("source rust"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn valid_extensions() {
+        assert!(is_valid_extension("rs"));
+        assert!(is_valid_extension("c++"));
+        assert!(is_valid_extension("sublime-settings"));
+        assert!(!is_valid_extension(""));
+        assert!(!is_valid_extension("tar.gz"));
+        assert!(!is_valid_extension("r s"));
+        assert!(!is_valid_extension("rü"));
+    }
+
+    #[tokio::test]
+    async fn default_syntax() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(db::Open::Memory)?;
+        let layer = Layer::new(db, 128);
+
+        let entry = crate::Entry {
+            text: "fn main() {}".to_string(),
+            ..Default::default()
+        };
+
+        let id = Id::from(1234);
+        layer.insert(id, entry, Quota::default()).await?;
+
+        let config = Config::default();
+        let plain = layer
+            .get_formatted(Key::new(id, None), None, &config)
+            .await?;
+        assert!(!plain.formatted.contains("source rust"));
+
+        let layer = Layer::new(layer.db.clone(), 128);
+
+        let config = Config {
+            default_syntax: Some("rust".to_string()),
+            ..Default::default()
+        };
+
+        let formatted = layer
+            .get_formatted(Key::new(id, None), None, &config)
+            .await?;
+        assert!(formatted.formatted.contains("source rust"));
+
+        let formatted = layer
+            .get_formatted(Key::new(id, Some("txt".to_string())), None, &config)
+            .await?;
+        assert!(!formatted.formatted.contains("source rust"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn detected_syntax() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(db::Open::Memory)?;
+        let layer = Layer::new(db, 128);
+        let config = Config {
+            default_syntax: Some("rust".to_string()),
+            ..Default::default()
+        };
+
+        let entry = crate::Entry {
+            text: "{\"foo\": 1}".to_string(),
+            ..Default::default()
+        };
+
+        let id = Id::from(1234);
+        layer.insert(id, entry, Quota::default()).await?;
+
+        let formatted = layer
+            .get_formatted(Key::new(id, None), None, &config)
+            .await?;
+        assert_eq!(formatted.extension.as_deref(), Some("json"));
+        assert!(formatted.formatted.contains("source json"));
+
+        let formatted = layer
+            .get_formatted(Key::new(id, Some("txt".to_string())), None, &config)
+            .await?;
+        assert_eq!(formatted.extension.as_deref(), Some("txt"));
+        assert!(!formatted.formatted.contains("source json"));
+
+        let entry = crate::Entry {
+            text: "{\"foo\": 1}".to_string(),
+            extension: Some("txt".to_string()),
+            ..Default::default()
+        };
+
+        let id = Id::from(5678);
+        layer.insert(id, entry, Quota::default()).await?;
+
+        let formatted = layer
+            .get_formatted(Key::new(id, None), None, &config)
+            .await?;
+        assert_eq!(formatted.extension.as_deref(), Some("txt"));
+        assert!(!formatted.formatted.contains("source json"));
 
         Ok(())
     }
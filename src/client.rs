@@ -0,0 +1,141 @@
+//! Typed client for the REST API of a wastebin instance, enabled with the `client` feature.
+
+use crate::rest::ErrorPayload;
+use reqwest::{Response, StatusCode};
+
+pub use crate::rest::RedirectResponse;
+pub use crate::Entry;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("request failed: {0}")]
+    Request(#[from] reqwest::Error),
+    #[error("server responded with {status}: {message}")]
+    Api { status: StatusCode, message: String },
+}
+
+/// Client talking to the instance at `base`, e.g. `https://bin.example.com`.
+#[derive(Clone)]
+pub struct Client {
+    base: String,
+    http: reqwest::Client,
+}
+
+impl RedirectResponse {
+    /// Identifier of the created paste without extension.
+    pub fn id(&self) -> &str {
+        let name = self.path.rsplit('/').next().unwrap_or_default();
+        name.split_once('.').map_or(name, |(id, _)| id)
+    }
+}
+
+impl Client {
+    pub fn new(base: impl Into<String>) -> Self {
+        Self::with_client(base, reqwest::Client::new())
+    }
+
+    /// Use a custom configured `http` client, e.g. to set timeouts.
+    pub fn with_client(base: impl Into<String>, http: reqwest::Client) -> Self {
+        let base = base.into().trim_end_matches('/').to_string();
+        Self { base, http }
+    }
+
+    fn url(&self, path: &str) -> String {
+        format!("{}/api/v1{path}", self.base)
+    }
+
+    /// Turn error responses into [`Error::Api`] with the message sent by the server.
+    async fn check(res: Response) -> Result<Response, Error> {
+        let status = res.status();
+
+        if status.is_success() {
+            return Ok(res);
+        }
+
+        let message = match res.json::<ErrorPayload>().await {
+            Ok(payload) => payload.message,
+            Err(_) => status.to_string(),
+        };
+
+        Err(Error::Api { status, message })
+    }
+
+    /// Create a new paste from `entry`.
+    pub async fn insert(&self, entry: &Entry) -> Result<RedirectResponse, Error> {
+        let res = self
+            .http
+            .post(self.url("/entries"))
+            .json(entry)
+            .send()
+            .await?;
+        Ok(Self::check(res).await?.json().await?)
+    }
+
+    /// Get the raw content of paste `id`.
+    pub async fn get_raw(&self, id: &str) -> Result<String, Error> {
+        let res = self
+            .http
+            .get(self.url(&format!("/entries/{id}")))
+            .send()
+            .await?;
+        Ok(Self::check(res).await?.text().await?)
+    }
+
+    /// Delete paste `id`, which is only possible within the deletion window.
+    pub async fn delete(&self, id: &str) -> Result<(), Error> {
+        let res = self
+            .http
+            .delete(self.url(&format!("/entries/{id}")))
+            .send()
+            .await?;
+        Self::check(res).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::{make_app, Client as TestServer};
+
+    #[tokio::test]
+    async fn insert_get_delete() -> Result<(), Box<dyn std::error::Error>> {
+        let server = TestServer::new(make_app()?);
+        let client = Client::new(server.base_url());
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            extension: Some("rs".to_string()),
+            ..Default::default()
+        };
+
+        let response = client.insert(&entry).await?;
+        assert!(response.path.ends_with(".rs"));
+
+        let id = response.id();
+        assert_eq!(client.get_raw(id).await?, "FooBarBaz");
+
+        client.delete(id).await?;
+
+        match client.get_raw(id).await {
+            Err(Error::Api { status, .. }) => assert_eq!(status, StatusCode::NOT_FOUND),
+            result => panic!("unexpected result {result:?}"),
+        }
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            extension: Some("../etc".to_string()),
+            ..Default::default()
+        };
+
+        match client.insert(&entry).await {
+            Err(Error::Api { status, message }) => {
+                assert_eq!(status, StatusCode::BAD_REQUEST);
+                assert_eq!(message, "invalid extension");
+            }
+            result => panic!("unexpected result {result:?}"),
+        }
+
+        Ok(())
+    }
+}
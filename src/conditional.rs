@@ -0,0 +1,151 @@
+use crate::Entry;
+use axum::headers::{CacheControl, ETag, HeaderMapExt, IfNoneMatch, LastModified};
+use axum::http::HeaderMap;
+use axum::TypedHeader;
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::time::{Duration, SystemTime};
+
+/// Pastes expiring within this many seconds are never stored by clients.
+const MIN_CACHE_LIFETIME: u32 = 60;
+
+/// Strong entity tag derived from `content`. Tags are only stable for a single build, which is
+/// fine as long as they are not persisted.
+pub fn etag<T: Hash + ?Sized>(content: &T) -> ETag {
+    let mut hasher = DefaultHasher::new();
+    content.hash(&mut hasher);
+
+    format!(r#""{:016x}""#, hasher.finish())
+        .parse()
+        .expect("valid entity tag")
+}
+
+/// Return `true` if the client already holds the representation tagged `etag`, i.e. a `304 Not
+/// Modified` response suffices.
+pub fn not_modified(if_none_match: &Option<TypedHeader<IfNoneMatch>>, etag: &ETag) -> bool {
+    if_none_match
+        .as_ref()
+        .is_some_and(|TypedHeader(header)| !header.precondition_passes(etag))
+}
+
+/// Return `true` if clients may store responses for `entry`. Burn after reading, password
+/// protected and soon expiring pastes must not be kept around.
+pub fn cacheable(entry: &Entry) -> bool {
+    !entry.burn_after_reading.unwrap_or(false)
+        && entry.ciphertext.is_none()
+        && entry
+            .expires
+            .is_none_or(|remaining| remaining >= MIN_CACHE_LIFETIME)
+}
+
+/// How clients may cache a paste response.
+pub enum Caching {
+    /// Never store the response.
+    NoStore,
+    /// Store the response but revalidate it with its entity tag before every use.
+    Revalidate {
+        etag: ETag,
+        last_modified: Option<SystemTime>,
+    },
+}
+
+impl Caching {
+    /// Caching of a paste representation identified by `content` if it is `cacheable`. Appendable
+    /// pastes change after creation, so only immutable ones report `Last-Modified`.
+    pub fn paste<T: Hash + ?Sized>(
+        cacheable: bool,
+        content: &T,
+        seconds_since_creation: u32,
+        appendable: bool,
+    ) -> Self {
+        if !cacheable {
+            return Self::NoStore;
+        }
+
+        let created = SystemTime::now() - Duration::from_secs(u64::from(seconds_since_creation));
+        let last_modified = (!appendable).then_some(created);
+
+        Self::Revalidate {
+            etag: etag(content),
+            last_modified,
+        }
+    }
+
+    /// Return `true` if a `304 Not Modified` response suffices, never for responses that must not
+    /// be stored.
+    pub fn not_modified(&self, if_none_match: &Option<TypedHeader<IfNoneMatch>>) -> bool {
+        match self {
+            Self::NoStore => false,
+            Self::Revalidate { etag, .. } => not_modified(if_none_match, etag),
+        }
+    }
+
+    pub fn headers(&self) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+
+        match self {
+            Self::NoStore => headers.typed_insert(CacheControl::new().with_no_store()),
+            Self::Revalidate {
+                etag,
+                last_modified,
+            } => {
+                headers.typed_insert(CacheControl::new().with_no_cache());
+                headers.typed_insert(etag.clone());
+
+                if let Some(time) = last_modified {
+                    headers.typed_insert(LastModified::from(*time));
+                }
+            }
+        }
+
+        headers
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn matching_tags() {
+        let tag = etag(b"foo");
+        assert_eq!(tag, etag(b"foo"));
+        assert_ne!(tag, etag(b"bar"));
+
+        assert!(!not_modified(&None, &tag));
+        assert!(not_modified(
+            &Some(TypedHeader(IfNoneMatch::from(tag.clone()))),
+            &tag
+        ));
+        assert!(!not_modified(
+            &Some(TypedHeader(IfNoneMatch::from(etag(b"bar")))),
+            &tag
+        ));
+        assert!(not_modified(&Some(TypedHeader(IfNoneMatch::any())), &tag));
+    }
+
+    #[test]
+    fn uncacheable_pastes() {
+        let mut entry = Entry {
+            expires: Some(3600),
+            ..Default::default()
+        };
+        assert!(cacheable(&entry));
+
+        entry.expires = Some(MIN_CACHE_LIFETIME - 1);
+        assert!(!cacheable(&entry));
+
+        entry.expires = None;
+        entry.burn_after_reading = Some(true);
+        assert!(!cacheable(&entry));
+
+        entry.burn_after_reading = None;
+        entry.ciphertext = Some(Vec::new());
+        assert!(!cacheable(&entry));
+
+        let caching = Caching::paste(false, "foo", 0, false);
+        let tag = etag("foo");
+        assert!(!caching.not_modified(&Some(TypedHeader(IfNoneMatch::from(tag)))));
+        assert_eq!(caching.headers()["cache-control"], "no-store");
+    }
+}
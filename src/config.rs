@@ -0,0 +1,798 @@
+use crate::db::Compression;
+use crate::highlight::DATA;
+use axum::http::StatusCode;
+use bytes::Bytes;
+use std::collections::{BTreeMap, HashSet};
+use std::env::{self, VarError};
+use std::fmt;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::Duration;
+use syntect::highlighting::{Theme, ThemeSet};
+use tower_http::CompressionLevel;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("{0} contains non-unicode data")]
+    NotUnicode(&'static str),
+    #[error("failed to parse {0}: {1}")]
+    Parse(&'static str, String),
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, std::io::Error),
+    #[error("{0} is set without {1}")]
+    Incomplete(&'static str, &'static str),
+    #[error("failed to load themes from {0}: {1}")]
+    Themes(PathBuf, syntect::LoadingError),
+}
+
+/// Behavior when a requested vanity slug is already taken.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlugCollision {
+    /// Reject the paste with 409 Conflict.
+    Reject,
+    /// Store the paste under a random identifier instead.
+    Random,
+}
+
+impl FromStr for SlugCollision {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "reject" => Ok(Self::Reject),
+            "random" => Ok(Self::Random),
+            _ => Err(format!("unknown slug collision behavior {s}")),
+        }
+    }
+}
+
+/// Treatment of trailing newlines of a paste.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum TrailingNewline {
+    /// Store the text as is.
+    Keep,
+    /// End the text with exactly one newline.
+    Add,
+    /// Remove all trailing newlines.
+    Strip,
+}
+
+impl FromStr for TrailingNewline {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "add" => Ok(Self::Add),
+            "strip" => Ok(Self::Strip),
+            _ => Err(format!("unknown trailing newline mode {s}")),
+        }
+    }
+}
+
+impl TrailingNewline {
+    /// Apply the mode to `text`. Added newlines follow the line ending style of the text.
+    pub fn apply(self, text: &mut String) {
+        if self == Self::Keep || text.is_empty() {
+            return;
+        }
+
+        let newline = if text.contains("\r\n") { "\r\n" } else { "\n" };
+        let len = text.trim_end_matches(['\n', '\r']).len();
+        text.truncate(len);
+
+        if self == Self::Add {
+            text.push_str(newline);
+        }
+    }
+}
+
+/// Treatment of control characters other than tab, line feed and carriage return.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum ControlCharacters {
+    /// Store the text as is.
+    Keep,
+    /// Reject the paste with 400 Bad Request.
+    Reject,
+    /// Remove the characters from the text.
+    Strip,
+}
+
+impl FromStr for ControlCharacters {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "keep" => Ok(Self::Keep),
+            "reject" => Ok(Self::Reject),
+            "strip" => Ok(Self::Strip),
+            _ => Err(format!("unknown control character mode {s}")),
+        }
+    }
+}
+
+/// Return `true` for control characters that are not tab, line feed or carriage return.
+pub fn is_disallowed_control(c: char) -> bool {
+    c.is_control() && !matches!(c, '\t' | '\n' | '\r')
+}
+
+impl ControlCharacters {
+    /// Apply the `Strip` mode to `text`, other modes leave it untouched.
+    pub fn apply(self, text: &mut String) {
+        if self == Self::Strip {
+            text.retain(|c| !is_disallowed_control(c));
+        }
+    }
+}
+
+/// Sources allowed to embed pastes in a frame.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum FrameAncestors {
+    /// Pastes cannot be framed at all.
+    None,
+    /// Only pages of the same origin may frame pastes.
+    SameOrigin,
+    /// Only the listed sources, e.g. `https://example.com`, may frame pastes.
+    Sources(Vec<String>),
+}
+
+impl FromStr for FrameAncestors {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.trim() {
+            "none" => Ok(Self::None),
+            "self" => Ok(Self::SameOrigin),
+            value => {
+                let sources: Vec<String> = value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|source| !source.is_empty())
+                    .map(ToString::to_string)
+                    .collect();
+
+                if sources.is_empty() {
+                    return Err("no frame ancestors given".to_string());
+                }
+
+                if let Some(source) = sources.iter().find(|source| {
+                    source.contains(|c: char| c.is_whitespace() || c == ';' || c == '\'')
+                }) {
+                    return Err(format!("invalid frame ancestor {source}"));
+                }
+
+                Ok(Self::Sources(sources))
+            }
+        }
+    }
+}
+
+impl FrameAncestors {
+    /// Content security policy directive restricting the embedders.
+    pub fn directive(&self) -> String {
+        match self {
+            Self::None => "frame-ancestors 'none'".to_string(),
+            Self::SameOrigin => "frame-ancestors 'self'".to_string(),
+            Self::Sources(sources) => format!("frame-ancestors {}", sources.join(" ")),
+        }
+    }
+
+    /// `X-Frame-Options` fallback for browsers ignoring the directive. There is no equivalent for
+    /// a list of sources, so no header is sent in that case.
+    pub fn x_frame_options(&self) -> Option<&'static str> {
+        match self {
+            Self::None => Some("DENY"),
+            Self::SameOrigin => Some("SAMEORIGIN"),
+            Self::Sources(_) => None,
+        }
+    }
+}
+
+/// Favicon served instead of the embedded PNG image.
+#[derive(Clone, Debug)]
+pub struct Favicon {
+    pub data: Bytes,
+    pub content_type: mime::Mime,
+}
+
+/// Description of the instance shown on the about page.
+#[derive(Clone, Debug, Default)]
+pub struct About {
+    pub description: Option<String>,
+    pub contact: Option<String>,
+    pub retention: Option<String>,
+}
+
+impl About {
+    /// Read the about page values from the environment, `None` if none of them is set.
+    fn from_env() -> Result<Option<Self>, Error> {
+        let about = Self {
+            description: parse_opt("WASTEBIN_ABOUT_DESCRIPTION")?,
+            contact: parse_opt("WASTEBIN_ABOUT_CONTACT")?,
+            retention: parse_opt("WASTEBIN_ABOUT_RETENTION")?,
+        };
+
+        if about.description.is_none() && about.contact.is_none() && about.retention.is_none() {
+            Ok(None)
+        } else {
+            Ok(Some(about))
+        }
+    }
+}
+
+/// PEM encoded certificate chain and private key to serve HTTPS with.
+#[derive(Clone, Debug)]
+pub struct Tls {
+    pub cert: PathBuf,
+    pub key: PathBuf,
+    /// Also serve HTTP/3 on the UDP port, which requires the `http3` feature.
+    pub http3: bool,
+}
+
+impl Tls {
+    /// Read the certificate and key paths from the environment, `None` if neither is set.
+    fn from_env() -> Result<Option<Self>, Error> {
+        let cert = parse_opt("WASTEBIN_TLS_CERT")?;
+        let key = parse_opt("WASTEBIN_TLS_KEY")?;
+        let http3 = parse("WASTEBIN_HTTP3", false)?;
+
+        match (cert, key) {
+            (Some(cert), Some(key)) => Ok(Some(Self { cert, key, http3 })),
+            (Some(_), None) => Err(Error::Incomplete("WASTEBIN_TLS_CERT", "WASTEBIN_TLS_KEY")),
+            (None, Some(_)) => Err(Error::Incomplete("WASTEBIN_TLS_KEY", "WASTEBIN_TLS_CERT")),
+            (None, None) if http3 => Err(Error::Incomplete("WASTEBIN_HTTP3", "WASTEBIN_TLS_CERT")),
+            (None, None) => Ok(None),
+        }
+    }
+}
+
+/// Runtime configuration shared by the web and REST handlers.
+#[derive(Clone, Debug)]
+pub struct Config {
+    /// Maximum size of a paste in UTF-8 encoded bytes.
+    pub max_body_size: usize,
+    /// Status code used to redirect after inserting a paste via the web form.
+    pub redirect_status: StatusCode,
+    /// Append a one-time `created` query flag to the redirect to show a success banner.
+    pub created_banner: bool,
+    /// Maximum number of lines of a paste.
+    pub max_lines: Option<usize>,
+    /// Number of proxies in front of the server whose `X-Forwarded-For` entries are trusted.
+    pub xff_trusted_hops: usize,
+    /// Build absolute paste URLs from the `X-Forwarded-Host` and `X-Forwarded-Proto` headers.
+    pub forwarded_host: bool,
+    /// Custom favicon.
+    pub favicon: Option<Favicon>,
+    /// Allow API clients to request a paste identifier.
+    pub vanity_slugs: bool,
+    /// Behavior when a requested vanity slug is already taken.
+    pub slug_collision: SlugCollision,
+    /// Treatment of trailing newlines on insert.
+    pub trailing_newline: TrailingNewline,
+    /// Extensions permitted for downloads. All extensions are allowed if not set.
+    pub download_extensions: Option<HashSet<String>>,
+    /// Download disallowed extensions as `txt` instead of rejecting them.
+    pub download_fallback: bool,
+    /// Bearer token required for the admin and metrics endpoints, which are disabled without one.
+    pub admin_token: Option<String>,
+    /// Syntax extension or name used to highlight pastes shown without an extension.
+    pub default_syntax: Option<String>,
+    /// Report success when deleting a paste that never existed.
+    pub delete_missing_ok: bool,
+    /// Send a `Content-Security-Policy` header restricting inline styles and scripts to nonces.
+    pub content_security_policy: bool,
+    /// Maximum length of the `<id>.<extension>` file name of downloads.
+    pub max_filename_length: usize,
+    /// Path prefix of redirect locations when served below a sub path, e.g. `/paste`.
+    pub base_path: String,
+    /// Redirect location for burn after reading pastes with `{path}` replaced by the paste path.
+    pub burn_redirect: Option<String>,
+    /// Treatment of control characters on insert.
+    pub control_characters: ControlCharacters,
+    /// Number of consecutive highlighting failures or timeouts after which a syntax is rendered as
+    /// plain text. Zero disables the fallback.
+    pub highlight_failures: u32,
+    /// Time after which a syntax disabled by `highlight_failures` is tried again.
+    pub highlight_cooldown: Duration,
+    /// Secret that, sent as `X-Delete-Secret` header, allows deleting pastes outside the deletion
+    /// window.
+    pub delete_secret: Option<String>,
+    /// Maximum length of paste extensions.
+    pub max_extension_length: usize,
+    /// Pre-select burn after reading on the web form.
+    pub default_burn: bool,
+    /// Length in bytes of a single line above which a paste is rendered as plain text. Zero
+    /// disables the fallback.
+    pub highlight_max_line_length: usize,
+    /// Maximum number of live pastes a single client address may hold.
+    pub max_pastes_per_ip: Option<usize>,
+    /// Include paste content in debug logs. Only meant for development.
+    pub log_content: bool,
+    /// Maximum number of live pastes in total.
+    pub max_pastes: Option<usize>,
+    /// Sources allowed to embed pastes via `/:id/embed`, sent as CSP `frame-ancestors` directive.
+    pub frame_ancestors: FrameAncestors,
+    /// Instance information shown on `/about`, which is only served if set.
+    pub about: Option<About>,
+    /// Compression level of responses and stored pastes.
+    pub compression_level: CompressionLevel,
+    /// Responses and stored pastes smaller than this many bytes are not compressed.
+    pub compression_min_size: u16,
+    /// Extensions of downloads that browsers may show inline instead of saving them.
+    pub inline_downloads: HashSet<String>,
+    /// Store the extension detected from the first line of pastes submitted without one and
+    /// redirect to it.
+    pub auto_extension: bool,
+    /// Maximum number of requests handled at the same time, further ones are rejected with 503.
+    pub max_concurrent_requests: Option<usize>,
+    /// Seconds after creation during which a paste can be deleted, zero disables deletion.
+    pub deletion_window: u64,
+    /// Maximum number of pastes a single client may create within a minute.
+    pub max_inserts_per_minute: Option<usize>,
+    /// Certificate and key to serve HTTPS, plain text HTTP if not set.
+    pub tls: Option<Tls>,
+    /// Highlighting themes, the built-in ones plus those loaded from `WASTEBIN_THEME_DIR`.
+    pub themes: BTreeMap<String, Theme>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            max_body_size: 1024 * 1024,
+            redirect_status: StatusCode::SEE_OTHER,
+            created_banner: false,
+            max_lines: None,
+            xff_trusted_hops: 0,
+            forwarded_host: false,
+            favicon: None,
+            vanity_slugs: false,
+            slug_collision: SlugCollision::Reject,
+            trailing_newline: TrailingNewline::Keep,
+            download_extensions: None,
+            download_fallback: false,
+            admin_token: None,
+            default_syntax: None,
+            delete_missing_ok: false,
+            content_security_policy: false,
+            max_filename_length: 255,
+            base_path: String::new(),
+            burn_redirect: None,
+            control_characters: ControlCharacters::Keep,
+            highlight_failures: 3,
+            highlight_cooldown: Duration::from_secs(300),
+            delete_secret: None,
+            max_extension_length: 16,
+            default_burn: false,
+            highlight_max_line_length: 16 * 1024,
+            max_pastes_per_ip: None,
+            log_content: false,
+            max_pastes: None,
+            frame_ancestors: FrameAncestors::None,
+            about: None,
+            compression_level: CompressionLevel::Default,
+            compression_min_size: 32,
+            inline_downloads: HashSet::new(),
+            auto_extension: false,
+            max_concurrent_requests: None,
+            deletion_window: 60,
+            max_inserts_per_minute: None,
+            tls: None,
+            themes: DATA.themes.themes.clone(),
+        }
+    }
+}
+
+/// Parse the environment variable `key` if it is set.
+fn parse_opt<T>(key: &'static str) -> Result<Option<T>, Error>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    match env::var(key) {
+        Ok(value) => value
+            .parse::<T>()
+            .map(Some)
+            .map_err(|err| Error::Parse(key, err.to_string())),
+        Err(VarError::NotPresent) => Ok(None),
+        Err(VarError::NotUnicode(_)) => Err(Error::NotUnicode(key)),
+    }
+}
+
+/// Parse the environment variable `key` or return `default` if it is not set.
+fn parse<T>(key: &'static str, default: T) -> Result<T, Error>
+where
+    T: FromStr,
+    T::Err: fmt::Display,
+{
+    Ok(parse_opt(key)?.unwrap_or(default))
+}
+
+/// Parse a redirection status code from `key`, one of 301, 302, 303, 307 or 308.
+fn redirect_status(key: &'static str, default: StatusCode) -> Result<StatusCode, Error> {
+    let status = parse(key, default)?;
+
+    match status {
+        StatusCode::MOVED_PERMANENTLY
+        | StatusCode::FOUND
+        | StatusCode::SEE_OTHER
+        | StatusCode::TEMPORARY_REDIRECT
+        | StatusCode::PERMANENT_REDIRECT => Ok(status),
+        _ => Err(Error::Parse(key, format!("{status} is not a redirection"))),
+    }
+}
+
+/// Parse a compression level from `key`, either `fastest`, `best`, `default` or an algorithm
+/// specific number.
+fn compression_level(key: &'static str) -> Result<CompressionLevel, Error> {
+    match parse_opt::<String>(key)?.as_deref() {
+        None | Some("default") => Ok(CompressionLevel::Default),
+        Some("fastest") => Ok(CompressionLevel::Fastest),
+        Some("best") => Ok(CompressionLevel::Best),
+        Some(value) => value
+            .parse()
+            .map(CompressionLevel::Precise)
+            .map_err(|_| Error::Parse(key, format!("unknown compression level {value}"))),
+    }
+}
+
+/// Built-in themes plus the `.tmTheme` files found in `dir` and its subdirectories, named after
+/// their file stem.
+fn load_themes(dir: &Path) -> Result<BTreeMap<String, Theme>, Error> {
+    let mut themes = ThemeSet {
+        themes: DATA.themes.themes.clone(),
+    };
+    themes
+        .add_from_folder(dir)
+        .map_err(|err| Error::Themes(dir.to_path_buf(), err))?;
+    Ok(themes.themes)
+}
+
+/// Parse a comma separated list of extensions. The special `syntaxes` entry expands to the
+/// extensions of all known syntaxes. `txt` is always included.
+fn download_extensions(value: &str) -> HashSet<String> {
+    let mut extensions = HashSet::from(["txt".to_string()]);
+
+    for entry in value.split(',').map(str::trim).filter(|s| !s.is_empty()) {
+        if entry == "syntaxes" {
+            extensions.extend(
+                DATA.syntax_set
+                    .syntaxes()
+                    .iter()
+                    .flat_map(|syntax| syntax.file_extensions.iter().cloned()),
+            );
+        } else {
+            extensions.insert(entry.to_string());
+        }
+    }
+
+    extensions
+}
+
+/// Extensions that are always downloaded as attachment because browsers would render them as
+/// active content.
+const ALWAYS_ATTACHMENT: [&str; 6] = ["htm", "html", "svg", "xht", "xhtml", "xml"];
+
+/// Content types that are always downloaded as attachment, whatever extension they are served for.
+const ACTIVE_CONTENT_TYPES: [&str; 3] = ["application/xml", "image/svg+xml", "text/html"];
+
+/// Parse a comma separated list of extensions that may be shown inline, dropping those that must
+/// always be downloaded as attachment.
+fn inline_downloads(value: &str) -> HashSet<String> {
+    value
+        .split(',')
+        .map(|ext| ext.trim().to_ascii_lowercase())
+        .filter(|ext| !ext.is_empty() && !ALWAYS_ATTACHMENT.contains(&ext.as_str()))
+        .collect()
+}
+
+/// Normalize a base path to a leading slash without trailing slashes, the root is empty.
+fn base_path(value: &str) -> String {
+    match value.trim().trim_matches('/') {
+        "" => String::new(),
+        path => format!("/{path}"),
+    }
+}
+
+impl Favicon {
+    /// Create a favicon from `data`, detecting the content type from magic bytes or else the
+    /// extension of `path`. Defaults to PNG.
+    pub fn new(path: &Path, data: Vec<u8>) -> Self {
+        let extension = path
+            .extension()
+            .and_then(|ext| ext.to_str())
+            .map(str::to_ascii_lowercase);
+
+        let content_type = if data.starts_with(b"\x89PNG") {
+            mime::IMAGE_PNG
+        } else if data.starts_with(&[0, 0, 1, 0]) || extension.as_deref() == Some("ico") {
+            "image/x-icon".parse().expect("valid mime type")
+        } else if data.starts_with(b"<svg") || extension.as_deref() == Some("svg") {
+            mime::IMAGE_SVG
+        } else {
+            mime::IMAGE_PNG
+        };
+
+        Self {
+            data: Bytes::from(data),
+            content_type,
+        }
+    }
+
+    /// Load favicon from `path`.
+    pub fn load(path: &Path) -> Result<Self, Error> {
+        let data = std::fs::read(path).map_err(|err| Error::Read(path.to_path_buf(), err))?;
+        Ok(Self::new(path, data))
+    }
+}
+
+impl Config {
+    /// Read configuration from `WASTEBIN_*` environment variables, falling back to defaults.
+    pub fn from_env() -> Result<Self, Error> {
+        let default = Self::default();
+
+        Ok(Self {
+            max_body_size: parse("WASTEBIN_MAX_BODY_SIZE", default.max_body_size)?,
+            redirect_status: redirect_status("WASTEBIN_REDIRECT_STATUS", default.redirect_status)?,
+            created_banner: parse("WASTEBIN_CREATED_BANNER", default.created_banner)?,
+            max_lines: parse_opt("WASTEBIN_MAX_LINES")?,
+            xff_trusted_hops: parse("WASTEBIN_XFF_TRUSTED_HOPS", default.xff_trusted_hops)?,
+            forwarded_host: parse("WASTEBIN_FORWARDED_HOST", default.forwarded_host)?,
+            favicon: parse_opt::<PathBuf>("WASTEBIN_FAVICON")?
+                .map(|path| Favicon::load(&path))
+                .transpose()?,
+            vanity_slugs: parse("WASTEBIN_VANITY_SLUGS", default.vanity_slugs)?,
+            slug_collision: parse("WASTEBIN_SLUG_COLLISION", default.slug_collision)?,
+            trailing_newline: parse("WASTEBIN_TRAILING_NEWLINE", default.trailing_newline)?,
+            download_extensions: parse_opt::<String>("WASTEBIN_DOWNLOAD_EXTENSIONS")?
+                .map(|value| download_extensions(&value)),
+            download_fallback: parse("WASTEBIN_DOWNLOAD_FALLBACK", default.download_fallback)?,
+            admin_token: parse_opt("WASTEBIN_ADMIN_TOKEN")?,
+            default_syntax: parse_opt("WASTEBIN_DEFAULT_SYNTAX")?,
+            delete_missing_ok: parse("WASTEBIN_DELETE_MISSING_OK", default.delete_missing_ok)?,
+            content_security_policy: parse(
+                "WASTEBIN_CONTENT_SECURITY_POLICY",
+                default.content_security_policy,
+            )?,
+            max_filename_length: parse(
+                "WASTEBIN_MAX_FILENAME_LENGTH",
+                default.max_filename_length,
+            )?,
+            base_path: parse_opt::<String>("WASTEBIN_BASE_PATH")?
+                .map_or_else(String::new, |value| base_path(&value)),
+            burn_redirect: parse_opt("WASTEBIN_BURN_REDIRECT")?,
+            control_characters: parse("WASTEBIN_STRIP_CONTROL", default.control_characters)?,
+            highlight_failures: parse("WASTEBIN_HIGHLIGHT_FAILURES", default.highlight_failures)?,
+            highlight_cooldown: Duration::from_secs(parse(
+                "WASTEBIN_HIGHLIGHT_COOLDOWN",
+                default.highlight_cooldown.as_secs(),
+            )?),
+            delete_secret: parse_opt("WASTEBIN_DELETE_SECRET")?,
+            max_extension_length: parse(
+                "WASTEBIN_MAX_EXTENSION_LENGTH",
+                default.max_extension_length,
+            )?,
+            default_burn: parse("WASTEBIN_DEFAULT_BURN", default.default_burn)?,
+            highlight_max_line_length: parse(
+                "WASTEBIN_HIGHLIGHT_MAX_LINE_LENGTH",
+                default.highlight_max_line_length,
+            )?,
+            max_pastes_per_ip: parse_opt("WASTEBIN_MAX_PASTES_PER_IP")?,
+            log_content: parse("WASTEBIN_LOG_CONTENT", default.log_content)?,
+            max_pastes: parse_opt("WASTEBIN_MAX_PASTES")?,
+            frame_ancestors: parse("WASTEBIN_FRAME_ANCESTORS", default.frame_ancestors)?,
+            about: About::from_env()?,
+            compression_level: compression_level("WASTEBIN_COMPRESSION_LEVEL")?,
+            compression_min_size: parse(
+                "WASTEBIN_COMPRESSION_MIN_SIZE",
+                default.compression_min_size,
+            )?,
+            inline_downloads: parse_opt::<String>("WASTEBIN_INLINE_DOWNLOADS")?
+                .map_or_else(HashSet::new, |value| inline_downloads(&value)),
+            auto_extension: parse("WASTEBIN_AUTO_EXTENSION", default.auto_extension)?,
+            max_concurrent_requests: parse_opt("WASTEBIN_MAX_CONCURRENT_REQUESTS")?,
+            deletion_window: parse("WASTEBIN_DELETION_WINDOW_SECS", default.deletion_window)?,
+            max_inserts_per_minute: parse_opt("WASTEBIN_MAX_INSERTS_PER_MINUTE")?,
+            tls: Tls::from_env()?,
+            themes: parse_opt::<PathBuf>("WASTEBIN_THEME_DIR")?
+                .map_or(Ok(default.themes), |path| load_themes(&path))?,
+        })
+    }
+
+    /// Size limit for the raw request body. Form encoding can inflate every byte of a paste to
+    /// three bytes, so the transport limit is more lenient than the paste limit itself.
+    pub fn max_request_size(&self) -> usize {
+        self.max_body_size.saturating_mul(3).saturating_add(1024)
+    }
+
+    /// zstd compression of stored pastes with the configured level and minimum size. The level
+    /// maps like the one of zstd compressed responses.
+    pub fn storage_compression(&self) -> Compression {
+        let level = match self.compression_level {
+            CompressionLevel::Fastest => 1,
+            CompressionLevel::Best => 21,
+            CompressionLevel::Precise(level) => {
+                let range = zstd::compression_level_range();
+                i32::try_from(level).map_or(*range.end(), |level| {
+                    level.clamp(*range.start(), *range.end())
+                })
+            }
+            _ => zstd::DEFAULT_COMPRESSION_LEVEL,
+        };
+
+        Compression {
+            level,
+            min_size: self.compression_min_size.into(),
+        }
+    }
+
+    /// `Content-Disposition` type for a download served with `extension` and `content_type`,
+    /// `attachment` unless the extension may be shown inline and the content is not active.
+    pub fn download_disposition(&self, extension: &str, content_type: &str) -> &'static str {
+        let active = ACTIVE_CONTENT_TYPES
+            .iter()
+            .any(|active| content_type.starts_with(active));
+
+        if !active
+            && self
+                .inline_downloads
+                .contains(&extension.to_ascii_lowercase())
+        {
+            "inline"
+        } else {
+            "attachment"
+        }
+    }
+
+    /// Redirect location of the burn page for a newly created paste at `path` below `base`.
+    pub fn burn_location(&self, base: &str, path: &str) -> String {
+        match &self.burn_redirect {
+            Some(template) => template.replace("{path}", path),
+            None => format!("{base}/burn{path}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn apply(mode: TrailingNewline, text: &str) -> String {
+        let mut text = text.to_string();
+        mode.apply(&mut text);
+        text
+    }
+
+    #[test]
+    fn trailing_newline_keep() {
+        assert_eq!(apply(TrailingNewline::Keep, "foo"), "foo");
+        assert_eq!(apply(TrailingNewline::Keep, "foo\n\n"), "foo\n\n");
+    }
+
+    #[test]
+    fn trailing_newline_add() {
+        assert_eq!(apply(TrailingNewline::Add, "foo"), "foo\n");
+        assert_eq!(apply(TrailingNewline::Add, "foo\n\n"), "foo\n");
+        assert_eq!(apply(TrailingNewline::Add, "foo\r\nbar"), "foo\r\nbar\r\n");
+        assert_eq!(apply(TrailingNewline::Add, ""), "");
+    }
+
+    #[test]
+    fn trailing_newline_strip() {
+        assert_eq!(apply(TrailingNewline::Strip, "foo"), "foo");
+        assert_eq!(apply(TrailingNewline::Strip, "foo\n"), "foo");
+        assert_eq!(apply(TrailingNewline::Strip, "foo\r\n\r\n"), "foo");
+    }
+
+    #[test]
+    fn strip_control_characters() {
+        let mut text = "a\0b\x1b[31mc\td\r\n\u{7f}".to_string();
+        ControlCharacters::Keep.apply(&mut text);
+        assert_eq!(text.len(), 14);
+
+        ControlCharacters::Strip.apply(&mut text);
+        assert_eq!(text, "ab[31mc\td\r\n");
+    }
+
+    #[test]
+    fn parse_download_extensions() {
+        let extensions = download_extensions("rs, md,,");
+        assert_eq!(extensions.len(), 3);
+        assert!(extensions.contains("rs"));
+        assert!(extensions.contains("md"));
+        assert!(extensions.contains("txt"));
+
+        let extensions = download_extensions("syntaxes");
+        assert!(extensions.contains("rs"));
+        assert!(extensions.contains("py"));
+        assert!(!extensions.contains("exe"));
+    }
+
+    #[test]
+    fn parse_frame_ancestors() {
+        assert_eq!("none".parse(), Ok(FrameAncestors::None));
+        assert_eq!("self".parse(), Ok(FrameAncestors::SameOrigin));
+        assert_eq!(
+            "https://example.com, https://*.example.org".parse(),
+            Ok(FrameAncestors::Sources(vec![
+                "https://example.com".to_string(),
+                "https://*.example.org".to_string()
+            ]))
+        );
+        assert!("".parse::<FrameAncestors>().is_err());
+        assert!("https://example.com; script-src *"
+            .parse::<FrameAncestors>()
+            .is_err());
+    }
+
+    #[test]
+    fn parse_inline_downloads() {
+        let extensions = inline_downloads("txt, CSV,html,,svg");
+        assert_eq!(extensions.len(), 2);
+        assert!(extensions.contains("txt"));
+        assert!(extensions.contains("csv"));
+    }
+
+    #[test]
+    fn normalize_base_path() {
+        assert_eq!(base_path("/"), "");
+        assert_eq!(base_path("paste"), "/paste");
+        assert_eq!(base_path("/paste/"), "/paste");
+        assert_eq!(base_path("/a/b"), "/a/b");
+    }
+
+    #[test]
+    fn parse_redirect_status() {
+        let key = "WASTEBIN_TEST_REDIRECT_STATUS";
+
+        for (value, valid) in [("301", true), ("308", true), ("300", false), ("304", false)] {
+            env::set_var(key, value);
+            assert_eq!(redirect_status(key, StatusCode::SEE_OTHER).is_ok(), valid);
+        }
+
+        env::remove_var(key);
+        assert_eq!(
+            redirect_status(key, StatusCode::SEE_OTHER).ok(),
+            Some(StatusCode::SEE_OTHER)
+        );
+    }
+
+    #[test]
+    fn detect_favicon_type() {
+        let favicon = Favicon::new(Path::new("icon"), b"\x89PNG\r\n".to_vec());
+        assert_eq!(favicon.content_type, mime::IMAGE_PNG);
+
+        let favicon = Favicon::new(Path::new("icon.svg"), b"<?xml".to_vec());
+        assert_eq!(favicon.content_type, mime::IMAGE_SVG);
+
+        let favicon = Favicon::new(Path::new("icon"), vec![0, 0, 1, 0, 1]);
+        assert_eq!(favicon.content_type.as_ref(), "image/x-icon");
+
+        let favicon = Favicon::new(Path::new("icon.bin"), vec![1, 2, 3]);
+        assert_eq!(favicon.content_type, mime::IMAGE_PNG);
+    }
+
+    #[test]
+    fn load_theme_dir() -> Result<(), Box<dyn std::error::Error>> {
+        let dir = env::temp_dir().join(format!("wastebin-themes-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(
+            dir.join("custom.tmTheme"),
+            include_str!("themes/ayu-dark.tmTheme"),
+        )?;
+
+        let themes = load_themes(&dir);
+        std::fs::remove_dir_all(&dir)?;
+
+        let themes = themes?;
+        assert!(themes.contains_key("custom"));
+        assert!(themes.contains_key("ayu-light"));
+        assert!(themes.contains_key("ayu-dark"));
+
+        assert!(matches!(
+            load_themes(&dir),
+            Err(Error::Themes(path, _)) if path == dir
+        ));
+
+        Ok(())
+    }
+}
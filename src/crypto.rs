@@ -0,0 +1,82 @@
+use crate::Error;
+use argon2::password_hash::rand_core::{OsRng, RngCore};
+use argon2::Argon2;
+use chacha20poly1305::aead::{Aead, KeyInit};
+use chacha20poly1305::{Key, XChaCha20Poly1305, XNonce};
+use tokio::task::spawn_blocking;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 24;
+
+/// Derive the XChaCha20-Poly1305 key for `password` and `salt` with argon2.
+fn cipher(password: &str, salt: &[u8]) -> Result<XChaCha20Poly1305, Error> {
+    let mut key = Key::default();
+
+    Argon2::default()
+        .hash_password_into(password.as_bytes(), salt, &mut key)
+        .map_err(|err| Error::Encryption(err.to_string()))?;
+
+    Ok(XChaCha20Poly1305::new(&key))
+}
+
+/// Encrypt `text` with a key derived from `password`. The result holds the random salt and nonce
+/// followed by the ciphertext.
+pub async fn encrypt(password: String, text: String) -> Result<Vec<u8>, Error> {
+    spawn_blocking(move || {
+        let mut salt = [0; SALT_LEN];
+        let mut nonce = [0; NONCE_LEN];
+        OsRng.fill_bytes(&mut salt);
+        OsRng.fill_bytes(&mut nonce);
+
+        let ciphertext = cipher(&password, &salt)?
+            .encrypt(XNonce::from_slice(&nonce), text.as_bytes())
+            .map_err(|err| Error::Encryption(err.to_string()))?;
+
+        let mut data = Vec::with_capacity(SALT_LEN + NONCE_LEN + ciphertext.len());
+        data.extend_from_slice(&salt);
+        data.extend_from_slice(&nonce);
+        data.extend_from_slice(&ciphertext);
+        Ok(data)
+    })
+    .await?
+}
+
+/// Decrypt `data` produced by [`encrypt`]. A wrong `password` fails with [`Error::Unauthorized`].
+pub async fn decrypt(password: String, data: Vec<u8>) -> Result<String, Error> {
+    spawn_blocking(move || {
+        if data.len() < SALT_LEN + NONCE_LEN {
+            return Err(Error::Encryption("ciphertext too short".to_string()));
+        }
+
+        let (salt, rest) = data.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let text = cipher(&password, salt)?
+            .decrypt(XNonce::from_slice(nonce), ciphertext)
+            .map_err(|_| Error::Unauthorized)?;
+
+        String::from_utf8(text).map_err(|err| Error::Encryption(err.to_string()))
+    })
+    .await?
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn encrypt_and_decrypt() -> Result<(), Error> {
+        let data = encrypt("hunter2".to_string(), "FooBarBaz".to_string()).await?;
+        assert!(!data.windows(9).any(|window| window == b"FooBarBaz"));
+
+        let text = decrypt("hunter2".to_string(), data.clone()).await?;
+        assert_eq!(text, "FooBarBaz");
+
+        assert!(matches!(
+            decrypt("hunter3".to_string(), data).await,
+            Err(Error::Unauthorized)
+        ));
+
+        Ok(())
+    }
+}
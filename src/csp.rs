@@ -0,0 +1,61 @@
+use axum::body::Body;
+use axum::http::{header, HeaderValue, Request};
+use axum::middleware::Next;
+use axum::response::Response;
+use http_body::Limited;
+use rand::Rng;
+
+tokio::task_local! {
+    static NONCE: String;
+}
+
+/// Nonce of the request currently being handled, to be set on inline `<style>` and `<script>`
+/// elements. Empty outside of the [`csp`] middleware.
+pub fn nonce() -> String {
+    NONCE.try_with(Clone::clone).unwrap_or_default()
+}
+
+fn random_nonce() -> String {
+    let mut rng = rand::thread_rng();
+    format!("{:032x}", rng.gen::<u128>())
+}
+
+/// Generate a fresh nonce for every request and, if `enabled`, send a `Content-Security-Policy`
+/// header with HTML responses that only allows inline styles and scripts carrying that nonce.
+pub async fn csp(
+    req: Request<Limited<Body>>,
+    next: Next<Limited<Body>>,
+    enabled: bool,
+) -> Response {
+    let nonce = random_nonce();
+    let mut response = NONCE.scope(nonce.clone(), next.run(req)).await;
+
+    let is_html = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|value| value.to_str().ok())
+        .is_some_and(|value| value.starts_with("text/html"));
+
+    if enabled && is_html {
+        let mut policy = format!(
+            "default-src 'self'; style-src 'self' 'nonce-{nonce}'; script-src 'nonce-{nonce}'"
+        );
+
+        // Keep directives set by the handler, e.g. `frame-ancestors` of embedded pastes.
+        if let Some(existing) = response
+            .headers()
+            .get(header::CONTENT_SECURITY_POLICY)
+            .and_then(|value| value.to_str().ok())
+        {
+            policy = format!("{policy}; {existing}");
+        }
+
+        if let Ok(value) = HeaderValue::from_str(&policy) {
+            response
+                .headers_mut()
+                .insert(header::CONTENT_SECURITY_POLICY, value);
+        }
+    }
+
+    response
+}
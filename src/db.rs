@@ -1,15 +1,76 @@
 use crate::id::Id;
-use crate::{Entry, Error};
-use once_cell::sync::Lazy;
-use rusqlite::{params, Connection};
+use crate::password;
+use crate::{deletion_time_remaining, Entry, Error};
+use rusqlite::{params, Connection, OptionalExtension};
 use rusqlite_migration::{Migrations, M};
+use std::io;
 use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
+use subtle::ConstantTimeEq;
 use tokio::task::spawn_blocking;
 
 #[derive(Clone)]
 pub struct Database {
     conn: Arc<Mutex<Connection>>,
+    compression: Option<Compression>,
+}
+
+/// zstd compression of stored texts.
+#[derive(Clone, Copy, Debug)]
+pub struct Compression {
+    /// zstd compression level.
+    pub level: i32,
+    /// Texts smaller than this many bytes are stored uncompressed.
+    pub min_size: usize,
+}
+
+impl Compression {
+    /// Split `text` into the `text` and `compressed` columns, the text is stored compressed only
+    /// if it reaches the minimum size.
+    fn apply(compression: Option<Self>, text: String) -> Result<(String, Option<Vec<u8>>), Error> {
+        match compression {
+            Some(Self { level, min_size }) if !text.is_empty() && text.len() >= min_size => {
+                let compressed =
+                    zstd::bulk::compress(text.as_bytes(), level).map_err(Error::Compression)?;
+                Ok((String::new(), Some(compressed)))
+            }
+            _ => Ok((text, None)),
+        }
+    }
+
+    /// Text of an entry stored as `text` and `compressed` columns.
+    fn restore(text: String, compressed: Option<Vec<u8>>) -> Result<String, Error> {
+        match compressed {
+            None => Ok(text),
+            Some(data) => {
+                let data = zstd::stream::decode_all(data.as_slice()).map_err(Error::Compression)?;
+                String::from_utf8(data).map_err(|err| {
+                    Error::Compression(io::Error::new(io::ErrorKind::InvalidData, err))
+                })
+            }
+        }
+    }
+}
+
+/// Outcome of deleting an entry within its deletion window.
+#[derive(Debug, PartialEq, Eq)]
+pub enum Deletion {
+    Deleted,
+    /// The entry has been deleted before.
+    AlreadyDeleted,
+    /// The entry never existed or has expired.
+    NotFound,
+    /// The deletion window has passed.
+    Expired,
+}
+
+/// Limits on live entries checked when inserting an entry.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Quota {
+    /// Maximum number of live entries in total.
+    pub max_entries: Option<usize>,
+    /// Maximum number of live entries with the client address of the inserted entry.
+    pub max_per_client: Option<usize>,
 }
 
 #[derive(Debug)]
@@ -18,14 +79,59 @@ pub enum Open {
     Path(PathBuf),
 }
 
-static MIGRATIONS: Lazy<Migrations> = Lazy::new(|| {
-    Migrations::new(vec![
+/// Ordered schema migrations. The schema version stored in the `user_version` pragma is the
+/// number of applied migrations.
+fn migrations() -> Vec<M<'static>> {
+    vec![
         M::up(include_str!("migrations/0001-up-initial.sql"))
             .down(include_str!("migrations/0001-down-initial.sql")),
         M::up(include_str!("migrations/0002-up-add-created-column.sql"))
             .down(include_str!("migrations/0002-down-add-created-column.sql")),
-    ])
-});
+        M::up(include_str!("migrations/0003-up-add-extension-column.sql")).down(include_str!(
+            "migrations/0003-down-add-extension-column.sql"
+        )),
+        M::up(include_str!("migrations/0004-up-add-tombstones.sql"))
+            .down(include_str!("migrations/0004-down-add-tombstones.sql")),
+        M::up(include_str!(
+            "migrations/0005-up-add-append-token-column.sql"
+        ))
+        .down(include_str!(
+            "migrations/0005-down-add-append-token-column.sql"
+        )),
+        M::up(include_str!("migrations/0006-up-add-filename-column.sql"))
+            .down(include_str!("migrations/0006-down-add-filename-column.sql")),
+        M::up(include_str!("migrations/0007-up-add-client-ip-column.sql")).down(include_str!(
+            "migrations/0007-down-add-client-ip-column.sql"
+        )),
+        M::up(include_str!("migrations/0008-up-add-ciphertext-column.sql")).down(include_str!(
+            "migrations/0008-down-add-ciphertext-column.sql"
+        )),
+        M::up(include_str!("migrations/0009-up-add-compressed-column.sql")).down(include_str!(
+            "migrations/0009-down-add-compressed-column.sql"
+        )),
+    ]
+}
+
+/// Apply all pending migrations one by one. Refuses databases created by a newer version with
+/// migrations unknown to this one.
+fn migrate(conn: &mut Connection) -> Result<(), Error> {
+    let steps = migrations();
+    let latest = steps.len();
+    let current: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+
+    if current > latest {
+        return Err(Error::SchemaTooNew(current, latest));
+    }
+
+    let migrations = Migrations::new(steps);
+
+    for version in current + 1..=latest {
+        tracing::info!(version, "migrating database schema");
+        migrations.to_version(conn, version)?;
+    }
+
+    Ok(())
+}
 
 impl Database {
     pub fn new(method: Open) -> Result<Self, Error> {
@@ -36,77 +142,291 @@ impl Database {
             Open::Path(path) => Connection::open(&path)?,
         };
 
-        MIGRATIONS.to_latest(&mut conn)?;
+        migrate(&mut conn)?;
 
         Ok(Self {
             conn: Arc::new(Mutex::new(conn)),
+            compression: None,
         })
     }
 
-    pub async fn insert(&self, id: Id, entry: Entry) -> Result<(), Error> {
+    /// Store texts compressed with `compression` from now on.
+    pub fn with_compression(mut self, compression: Compression) -> Self {
+        self.compression = Some(compression);
+        self
+    }
+
+    /// Insert `entry` unless that exceeds `quota`. Live entries are counted in the same transaction
+    /// as the insert, so that concurrent inserts cannot exceed the limits.
+    pub async fn insert(&self, id: Id, entry: Entry, quota: Quota) -> Result<(), Error> {
         let conn = self.conn.clone();
         let id = id.as_u32();
+        let client_ip = entry.client_ip.map(|ip| ip.to_string());
+        let compression = self.compression;
 
-        spawn_blocking(move || match entry.expires {
-            None => conn.lock().unwrap().execute(
-                "INSERT INTO entries (id, text, burn_after_reading, created_at) VALUES (?1, ?2, ?3, datetime('now'))",
-                params![id, entry.text, entry.burn_after_reading],
-            ),
-            Some(expires) => conn.lock().unwrap().execute(
-                "INSERT INTO entries (id, text, burn_after_reading, expires, created_at) VALUES (?1, ?2, ?3, datetime('now', ?4), datetime('now'))",
-                params![
-                    id,
-                    entry.text,
-                    entry.burn_after_reading,
-                    format!("{expires} seconds")
-                ],
-            ),
+        spawn_blocking(move || {
+            let (text, compressed) = Compression::apply(compression, entry.text)?;
+            let mut conn = conn.lock().unwrap();
+            let tx = conn.transaction()?;
+
+            if let Some(max) = quota.max_entries {
+                let count: usize = tx.query_row(
+                    "SELECT COUNT(*) FROM entries WHERE expires IS NULL OR expires > datetime('now')",
+                    [],
+                    |row| row.get(0),
+                )?;
+
+                if count >= max {
+                    return Err(Error::AtCapacity(max));
+                }
+            }
+
+            if let (Some(max), Some(ip)) = (quota.max_per_client, &client_ip) {
+                let count: usize = tx.query_row(
+                    "SELECT COUNT(*) FROM entries WHERE client_ip=?1 AND (expires IS NULL OR expires > datetime('now'))",
+                    params![ip],
+                    |row| row.get(0),
+                )?;
+
+                if count >= max {
+                    return Err(Error::QuotaExceeded(max));
+                }
+            }
+
+            match entry.expires {
+                None => tx.execute(
+                    "INSERT INTO entries (id, text, extension, burn_after_reading, append_token, filename, client_ip, ciphertext, compressed, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, datetime('now'))",
+                    params![
+                        id,
+                        text,
+                        entry.extension,
+                        entry.burn_after_reading,
+                        entry.append_token,
+                        entry.filename,
+                        client_ip,
+                        entry.ciphertext,
+                        compressed
+                    ],
+                ),
+                Some(expires) => tx.execute(
+                    "INSERT INTO entries (id, text, extension, burn_after_reading, append_token, filename, client_ip, ciphertext, compressed, expires, created_at) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, datetime('now', ?10), datetime('now'))",
+                    params![
+                        id,
+                        text,
+                        entry.extension,
+                        entry.burn_after_reading,
+                        entry.append_token,
+                        entry.filename,
+                        client_ip,
+                        entry.ciphertext,
+                        compressed,
+                        format!("{expires} seconds")
+                    ],
+                ),
+            }
+            .map_err(|err| match err {
+                rusqlite::Error::SqliteFailure(ref failure, _)
+                    if failure.code == rusqlite::ErrorCode::ConstraintViolation =>
+                {
+                    Error::IdTaken
+                }
+                err => Error::Sqlite(err),
+            })?;
+
+            tx.commit()?;
+
+            Ok(())
         })
-        .await??;
+        .await?
+    }
 
-        Ok(())
+    /// Get entry `id` and delete it if it is marked as burn after reading. Protected entries are
+    /// only returned, decrypted, if `password` matches, failed attempts do not consume them. Of
+    /// concurrent reads of a burn after reading entry only the one that deletes it returns it.
+    pub async fn get(&self, id: Id, password: Option<String>) -> Result<Entry, Error> {
+        let mut entry = self.peek(id).await?;
+        password::unlock(&mut entry, password).await?;
+
+        if entry.burn_after_reading.unwrap_or(false) && self.delete(id).await? == 0 {
+            return Err(Error::NotFound);
+        }
+
+        Ok(entry)
     }
 
-    pub async fn get(&self, id: Id) -> Result<Entry, Error> {
+    /// Get entry `id` without deleting it if it is marked as burn after reading.
+    pub async fn peek(&self, id: Id) -> Result<Entry, Error> {
         let conn = self.conn.clone();
-        let id_as_u32 = id.as_u32();
+        let id = id.as_u32();
 
-        let entry = spawn_blocking(move || {
-            conn.lock().unwrap().query_row(
-                "SELECT text, burn_after_reading, CAST(((julianday('now') - julianday(created_at)) * 24 * 60 * 60) AS INT) FROM entries WHERE id=?1",
-                params![id_as_u32],
+        spawn_blocking(move || {
+            let (mut entry, compressed) = conn.lock().unwrap().query_row(
+                "SELECT text, extension, burn_after_reading, CAST(((julianday('now') - julianday(created_at)) * 24 * 60 * 60) AS INT), append_token IS NOT NULL, filename, MAX(0, CAST(((julianday(expires) - julianday('now')) * 24 * 60 * 60) AS INT)), ciphertext, compressed FROM entries WHERE id=?1",
+                params![id],
                 |row| {
-                    Ok(Entry {
+                    let entry = Entry {
                         text: row.get(0)?,
-                        extension: None,
-                        expires: None,
-                        burn_after_reading: row.get(1)?,
-                        seconds_since_creation: row.get(2)?,
-                    })
+                        extension: row.get(1)?,
+                        expires: row.get(6)?,
+                        burn_after_reading: row.get(2)?,
+                        seconds_since_creation: row.get(3)?,
+                        slug: None,
+                        appendable: row.get(4)?,
+                        append_token: None,
+                        filename: row.get(5)?,
+                        client_ip: None,
+                        password: None,
+                        ciphertext: row.get(7)?,
+                    };
+
+                    Ok((entry, row.get(8)?))
                 },
-            )
+            )?;
+
+            entry.text = Compression::restore(entry.text, compressed)?;
+            Ok(entry)
+        })
+        .await?
+    }
+
+    /// Delete entry `id` and return the number of deleted entries, zero if it did not exist.
+    pub async fn delete(&self, id: Id) -> Result<usize, Error> {
+        let conn = self.conn.clone();
+        let id = id.as_u32();
+
+        let deleted = spawn_blocking(move || {
+            conn.lock()
+                .unwrap()
+                .execute("DELETE FROM entries WHERE id=?1", params![id])
         })
         .await??;
 
-        if entry.burn_after_reading.unwrap_or(false) {
-            self.delete(id).await?;
-        }
+        Ok(deleted)
+    }
 
-        Ok(entry)
+    /// Delete entry `id` if it is still within the deletion `window` in seconds, any entry if
+    /// `window` is `None`. Existence check and deletion happen in a single transaction and deleted
+    /// entries are remembered for a day.
+    pub async fn delete_recent(&self, id: Id, window: Option<u64>) -> Result<Deletion, Error> {
+        let conn = self.conn.clone();
+        let id = id.as_u32();
+
+        spawn_blocking(move || {
+            let mut conn = conn.lock().unwrap();
+            let tx = conn.transaction()?;
+
+            let age: Option<u32> = tx
+                .query_row(
+                    "SELECT CAST(((julianday('now') - julianday(created_at)) * 24 * 60 * 60) AS INT) FROM entries WHERE id=?1",
+                    params![id],
+                    |row| row.get(0),
+                )
+                .optional()?;
+
+            let deletion = match age {
+                Some(age)
+                    if window.is_some_and(|window| {
+                        deletion_time_remaining(age, window).is_none()
+                    }) =>
+                {
+                    Deletion::Expired
+                }
+                Some(_) => {
+                    tx.execute("DELETE FROM entries WHERE id=?1", params![id])?;
+                    tx.execute(
+                        "INSERT OR REPLACE INTO tombstones (id, deleted_at) VALUES (?1, datetime('now'))",
+                        params![id],
+                    )?;
+                    Deletion::Deleted
+                }
+                None => {
+                    let deleted: bool = tx.query_row(
+                        "SELECT EXISTS(SELECT 1 FROM tombstones WHERE id=?1)",
+                        params![id],
+                        |row| row.get(0),
+                    )?;
+
+                    if deleted {
+                        Deletion::AlreadyDeleted
+                    } else {
+                        Deletion::NotFound
+                    }
+                }
+            };
+
+            tx.commit()?;
+
+            Ok(deletion)
+        })
+        .await?
     }
 
-    pub async fn delete(&self, id: Id) -> Result<(), Error> {
+    /// Append `text` to entry `id` if `token` matches its append token. The combined text is
+    /// passed to `check` before it is stored. Returns the new size in bytes.
+    pub async fn append<F>(
+        &self,
+        id: Id,
+        token: String,
+        text: String,
+        check: F,
+    ) -> Result<usize, Error>
+    where
+        F: FnOnce(&str) -> Result<(), Error> + Send + 'static,
+    {
         let conn = self.conn.clone();
         let id = id.as_u32();
+        let compression = self.compression;
 
         spawn_blocking(move || {
-            conn.lock()
-                .unwrap()
-                .execute("DELETE FROM entries WHERE id=?1", params![id])
+            let mut conn = conn.lock().unwrap();
+            let tx = conn.transaction()?;
+
+            let (content, compressed, expected): (String, Option<Vec<u8>>, Option<String>) = tx
+                .query_row(
+                    "SELECT text, compressed, append_token FROM entries WHERE id=?1",
+                    params![id],
+                    |row| Ok((row.get(0)?, row.get(1)?, row.get(2)?)),
+                )?;
+
+            match expected {
+                Some(expected) if bool::from(expected.as_bytes().ct_eq(token.as_bytes())) => {}
+                Some(_) => return Err(Error::Unauthorized),
+                // Entries created without append mode accept no token at all.
+                None => return Err(Error::Unauthorized),
+            }
+
+            let mut content = Compression::restore(content, compressed)?;
+            content.push_str(&text);
+            check(&content)?;
+
+            let size = content.len();
+            let (content, compressed) = Compression::apply(compression, content)?;
+
+            tx.execute(
+                "UPDATE entries SET text=?2, compressed=?3 WHERE id=?1",
+                params![id, content, compressed],
+            )?;
+            tx.commit()?;
+
+            Ok(size)
+        })
+        .await?
+    }
+
+    /// Count all unexpired entries.
+    pub async fn count(&self) -> Result<usize, Error> {
+        let conn = self.conn.clone();
+
+        let count = spawn_blocking(move || {
+            conn.lock().unwrap().query_row(
+                "SELECT COUNT(*) FROM entries WHERE expires IS NULL OR expires > datetime('now')",
+                [],
+                |row| row.get(0),
+            )
         })
         .await??;
 
-        Ok(())
+        Ok(count)
     }
 
     /// Remove all expired entries and return their `Id`s.
@@ -126,6 +446,10 @@ impl Database {
                 .collect::<Result<Vec<_>, _>>()?;
 
             conn.execute("DELETE FROM entries WHERE expires < datetime('now')", [])?;
+            conn.execute(
+                "DELETE FROM tombstones WHERE deleted_at < datetime('now', '-1 day')",
+                [],
+            )?;
 
             Ok(ids)
         })
@@ -136,6 +460,45 @@ impl Database {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use axum::http::StatusCode;
+    use std::net::IpAddr;
+
+    #[tokio::test]
+    async fn migrate_old_schema() -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = Connection::open_in_memory()?;
+        Migrations::new(migrations().into_iter().take(3).collect()).to_latest(&mut conn)?;
+
+        conn.execute(
+            "INSERT INTO entries (id, text, extension, created_at) VALUES (?1, ?2, ?3, datetime('now'))",
+            params![1234, "hello world", "rs"],
+        )?;
+
+        migrate(&mut conn)?;
+
+        let version: usize = conn.pragma_query_value(None, "user_version", |row| row.get(0))?;
+        assert_eq!(version, migrations().len());
+
+        let db = Database {
+            conn: Arc::new(Mutex::new(conn)),
+            compression: None,
+        };
+
+        let entry = db.get(Id::from(1234), None).await?;
+        assert_eq!(entry.text, "hello world");
+        assert_eq!(entry.extension.as_deref(), Some("rs"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn refuse_newer_schema() -> Result<(), Box<dyn std::error::Error>> {
+        let mut conn = Connection::open_in_memory()?;
+        conn.pragma_update(None, "user_version", migrations().len() + 1)?;
+
+        assert!(matches!(migrate(&mut conn), Err(Error::SchemaTooNew(..))));
+
+        Ok(())
+    }
 
     #[tokio::test]
     async fn insert() -> Result<(), Box<dyn std::error::Error>> {
@@ -143,18 +506,25 @@ mod tests {
 
         let entry = Entry {
             text: "hello world".to_string(),
+            extension: Some("rs".to_string()),
+            filename: Some("hello.rs".to_string()),
             ..Default::default()
         };
 
         let id = Id::from(1234);
-        db.insert(id, entry).await?;
+        db.insert(id, entry, Quota::default()).await?;
 
-        let entry = db.get(id).await?;
+        let entry = db.get(id, None).await?;
         assert_eq!(entry.text, "hello world");
+        assert_eq!(entry.extension.as_deref(), Some("rs"));
+        assert_eq!(entry.filename.as_deref(), Some("hello.rs"));
 
-        let result = db.get(Id::from(5678)).await;
+        let result = db.get(Id::from(5678), None).await;
         assert!(result.is_err());
 
+        let result = db.insert(id, Entry::default(), Quota::default()).await;
+        assert!(matches!(result, Err(Error::IdTaken)));
+
         Ok(())
     }
 
@@ -166,9 +536,67 @@ mod tests {
             ..Default::default()
         };
         let id = Id::from(1234);
-        db.insert(id, entry).await?;
-        assert!(db.get(id).await.is_ok());
-        assert!(db.get(id).await.is_err());
+        db.insert(id, entry, Quota::default()).await?;
+        assert!(db.peek(id).await.is_ok());
+        assert!(db.get(id, None).await.is_ok());
+        assert!(db.get(id, None).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn encrypted_at_rest() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(Open::Memory)?;
+        let mut entry = Entry {
+            text: "FooBarBaz".to_string(),
+            password: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+        password::protect(&mut entry).await?;
+
+        let id = Id::from(1234);
+        db.insert(id, entry, Quota::default()).await?;
+
+        let (text, ciphertext): (String, Vec<u8>) = db.conn.lock().unwrap().query_row(
+            "SELECT text, ciphertext, compressed FROM entries WHERE id=?1",
+            params![id.as_u32()],
+            |row| Ok((row.get(0)?, row.get(1)?)),
+        )?;
+        assert!(text.is_empty());
+        assert!(!ciphertext.windows(9).any(|window| window == b"FooBarBaz"));
+
+        assert!(db.peek(id).await?.text.is_empty());
+        assert!(matches!(
+            db.get(id, Some("hunter3".to_string())).await,
+            Err(Error::Unauthorized)
+        ));
+        assert_eq!(
+            db.get(id, Some("hunter2".to_string())).await?.text,
+            "FooBarBaz"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn burn_after_reading_race() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(Open::Memory)?;
+        let entry = Entry {
+            text: "secret".to_string(),
+            burn_after_reading: Some(true),
+            ..Default::default()
+        };
+        let id = Id::from(1234);
+        db.insert(id, entry, Quota::default()).await?;
+
+        let (first, second) = tokio::join!(db.get(id, None), db.get(id, None));
+        let (won, lost): (Vec<_>, Vec<_>) = [first, second].into_iter().partition(Result::is_ok);
+
+        assert_eq!(won.len(), 1);
+        assert_eq!(won.into_iter().next().unwrap()?.text, "secret");
+
+        let err = lost.into_iter().next().unwrap().unwrap_err();
+        assert_eq!(StatusCode::from(err), StatusCode::NOT_FOUND);
 
         Ok(())
     }
@@ -183,14 +611,14 @@ mod tests {
         };
 
         let id = Id::from(1234);
-        db.insert(id, entry).await?;
-        assert!(db.get(id).await.is_ok());
+        db.insert(id, entry, Quota::default()).await?;
+        assert!(db.get(id, None).await.is_ok());
 
         tokio::time::sleep(std::time::Duration::from_millis(2000)).await;
-        assert!(db.get(id).await.unwrap().seconds_since_creation >= 1);
+        assert!(db.get(id, None).await.unwrap().seconds_since_creation >= 1);
 
         db.purge().await?;
-        assert!(db.get(id).await.is_err());
+        assert!(db.get(id, None).await.is_err());
 
         Ok(())
     }
@@ -200,11 +628,185 @@ mod tests {
         let db = Database::new(Open::Memory)?;
 
         let id = Id::from(1234);
-        db.insert(id, Entry::default()).await?;
+        db.insert(id, Entry::default(), Quota::default()).await?;
+
+        assert!(db.get(id, None).await.is_ok());
+        assert_eq!(db.delete(id).await?, 1);
+        assert!(db.get(id, None).await.is_err());
+        assert_eq!(db.delete(id).await?, 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delete_recent() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(Open::Memory)?;
+
+        let id = Id::from(1234);
+        db.insert(id, Entry::default(), Quota::default()).await?;
+
+        assert_eq!(db.delete_recent(id, Some(60)).await?, Deletion::Deleted);
+        assert!(db.get(id, None).await.is_err());
+        assert_eq!(
+            db.delete_recent(id, Some(60)).await?,
+            Deletion::AlreadyDeleted
+        );
+        assert_eq!(
+            db.delete_recent(Id::from(5678), Some(60)).await?,
+            Deletion::NotFound
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delete_outside_window() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(Open::Memory)?;
+
+        let id = Id::from(1234);
+        db.insert(id, Entry::default(), Quota::default()).await?;
+
+        db.conn.lock().unwrap().execute(
+            "UPDATE entries SET created_at = datetime('now', '-1 hour') WHERE id=?1",
+            params![id.as_u32()],
+        )?;
+
+        assert_eq!(db.delete_recent(id, Some(60)).await?, Deletion::Expired);
+        assert_eq!(db.delete_recent(id, Some(0)).await?, Deletion::Expired);
+        assert_eq!(db.delete_recent(id, None).await?, Deletion::Deleted);
+
+        let id = Id::from(5678);
+        db.insert(id, Entry::default(), Quota::default()).await?;
+
+        db.conn.lock().unwrap().execute(
+            "UPDATE entries SET created_at = datetime('now', '-1 hour') WHERE id=?1",
+            params![id.as_u32()],
+        )?;
+
+        assert_eq!(db.delete_recent(id, Some(7200)).await?, Deletion::Deleted);
+        assert!(db.get(id, None).await.is_err());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn insert_within_quota() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(Open::Memory)?;
+        let ip: IpAddr = "10.0.0.1".parse()?;
+
+        let entry = Entry {
+            client_ip: Some(ip),
+            ..Default::default()
+        };
+
+        let quota = Quota {
+            max_entries: Some(3),
+            max_per_client: Some(2),
+        };
+
+        db.insert(Id::from(1), entry.clone(), quota).await?;
+        db.insert(Id::from(2), entry.clone(), quota).await?;
+        assert!(matches!(
+            db.insert(Id::from(3), entry.clone(), quota).await,
+            Err(Error::QuotaExceeded(2))
+        ));
+
+        let other = Entry {
+            client_ip: Some("10.0.0.2".parse()?),
+            ..Default::default()
+        };
+
+        db.insert(Id::from(4), other.clone(), quota).await?;
+        assert!(matches!(
+            db.insert(Id::from(5), other, quota).await,
+            Err(Error::AtCapacity(3))
+        ));
+        assert_eq!(db.count().await?, 3);
+
+        db.delete(Id::from(1)).await?;
+        db.insert(Id::from(3), entry, quota).await?;
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compressed_at_rest() -> Result<(), Box<dyn std::error::Error>> {
+        let compression = Compression {
+            level: 19,
+            min_size: 1024,
+        };
+        let db = Database::new(Open::Memory)?.with_compression(compression);
 
-        assert!(db.get(id).await.is_ok());
-        assert!(db.delete(id).await.is_ok());
-        assert!(db.get(id).await.is_err());
+        let stored = |id: Id| -> Result<(String, Option<Vec<u8>>), rusqlite::Error> {
+            db.conn.lock().unwrap().query_row(
+                "SELECT text, compressed FROM entries WHERE id=?1",
+                params![id.as_u32()],
+                |row| Ok((row.get(0)?, row.get(1)?)),
+            )
+        };
+
+        let small = Entry {
+            text: "small".to_string(),
+            ..Default::default()
+        };
+
+        db.insert(Id::from(1), small, Quota::default()).await?;
+        assert_eq!(stored(Id::from(1))?, ("small".to_string(), None));
+
+        let text = "large\n".repeat(512);
+        let large = Entry {
+            text: text.clone(),
+            ..Default::default()
+        };
+
+        db.insert(Id::from(2), large, Quota::default()).await?;
+        let (column, compressed) = stored(Id::from(2))?;
+        assert!(column.is_empty());
+        assert_eq!(compressed, Some(zstd::bulk::compress(text.as_bytes(), 19)?));
+        assert_eq!(db.peek(Id::from(2)).await?.text, text);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn append() -> Result<(), Box<dyn std::error::Error>> {
+        let db = Database::new(Open::Memory)?;
+
+        let entry = Entry {
+            text: "Foo".to_string(),
+            append_token: Some("token".to_string()),
+            ..Default::default()
+        };
+
+        let id = Id::from(1234);
+        db.insert(id, entry, Quota::default()).await?;
+
+        let size = db
+            .append(id, "token".to_string(), "Bar".to_string(), |_| Ok(()))
+            .await?;
+        assert_eq!(size, 6);
+        assert_eq!(db.get(id, None).await?.text, "FooBar");
+
+        let result = db
+            .append(id, "wrong".to_string(), "Baz".to_string(), |_| Ok(()))
+            .await;
+        assert!(matches!(result, Err(Error::Unauthorized)));
+
+        let result = db
+            .append(id, "token".to_string(), "Baz".to_string(), |_| {
+                Err(Error::PasteTooLarge(6))
+            })
+            .await;
+        assert!(matches!(result, Err(Error::PasteTooLarge(6))));
+        assert_eq!(db.get(id, None).await?.text, "FooBar");
+
+        let id = Id::from(5678);
+        db.insert(id, Entry::default(), Quota::default()).await?;
+
+        let result = db
+            .append(id, String::new(), "Baz".to_string(), |_| Ok(()))
+            .await;
+        assert!(matches!(result, Err(Error::Unauthorized)));
 
         Ok(())
     }
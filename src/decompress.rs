@@ -0,0 +1,132 @@
+use axum::body::Body;
+use axum::http::{header, Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::Response;
+use http_body::Limited;
+use std::io::{self, Read};
+
+#[derive(Clone, Copy, Debug)]
+enum Encoding {
+    Gzip,
+    Zstd,
+}
+
+/// Read at most `limit` decompressed bytes from `reader`. Returns `None` if there is more data.
+fn read_limited(reader: impl Read, limit: usize) -> io::Result<Option<Vec<u8>>> {
+    let mut data = Vec::new();
+    reader.take(limit as u64 + 1).read_to_end(&mut data)?;
+
+    if data.len() > limit {
+        Ok(None)
+    } else {
+        Ok(Some(data))
+    }
+}
+
+fn decode(encoding: Encoding, data: &[u8], limit: usize) -> io::Result<Option<Vec<u8>>> {
+    match encoding {
+        Encoding::Gzip => read_limited(flate2::read::GzDecoder::new(data), limit),
+        Encoding::Zstd => read_limited(zstd::stream::read::Decoder::new(data)?, limit),
+    }
+}
+
+/// Decompress request bodies sent with a `gzip` or `zstd` content encoding. The compressed size
+/// is already bounded by the outer body limit, the decompressed size is capped at `limit` bytes
+/// to guard against decompression bombs.
+pub async fn decompress(
+    req: Request<Limited<Body>>,
+    next: Next<Limited<Body>>,
+    limit: usize,
+) -> Result<Response, StatusCode> {
+    let encoding = match req.headers().get(header::CONTENT_ENCODING) {
+        None => return Ok(next.run(req).await),
+        Some(value) => match value.to_str().map(str::trim) {
+            Ok("identity") => return Ok(next.run(req).await),
+            Ok("gzip" | "x-gzip") => Encoding::Gzip,
+            Ok("zstd") => Encoding::Zstd,
+            _ => return Err(StatusCode::UNSUPPORTED_MEDIA_TYPE),
+        },
+    };
+
+    let (mut parts, body) = req.into_parts();
+
+    let compressed = hyper::body::to_bytes(body)
+        .await
+        .map_err(|_| StatusCode::PAYLOAD_TOO_LARGE)?;
+
+    let decompressed = tokio::task::spawn_blocking(move || decode(encoding, &compressed, limit))
+        .await
+        .map_err(|_| StatusCode::INTERNAL_SERVER_ERROR)?
+        .map_err(|_| StatusCode::BAD_REQUEST)?
+        .ok_or(StatusCode::PAYLOAD_TOO_LARGE)?;
+
+    parts.headers.remove(header::CONTENT_ENCODING);
+    parts.headers.remove(header::CONTENT_LENGTH);
+
+    let body = Limited::new(Body::from(decompressed), limit);
+
+    Ok(next.run(Request::from_parts(parts, body)).await)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::test_helpers::{make_app, Client};
+    use crate::Entry;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use http::StatusCode;
+    use std::io::Write;
+
+    fn gzip(data: &[u8]) -> Result<Vec<u8>, std::io::Error> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        encoder.finish()
+    }
+
+    #[tokio::test]
+    async fn gzip_upload() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client
+            .post("/api/entries")
+            .header("content-type", "application/json")
+            .header("content-encoding", "gzip")
+            .body(gzip(&serde_json::to_vec(&entry)?)?)
+            .send()
+            .await?;
+
+        assert_eq!(res.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn decompression_bomb() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "a".repeat(1024 * 1024),
+            ..Default::default()
+        };
+
+        let body = gzip(&serde_json::to_vec(&entry)?)?;
+        assert!(body.len() < 4096);
+
+        let res = client
+            .post("/api/entries")
+            .header("content-type", "application/json")
+            .header("content-encoding", "gzip")
+            .body(body)
+            .send()
+            .await?;
+
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        Ok(())
+    }
+}
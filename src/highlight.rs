@@ -1,9 +1,15 @@
+use crate::cache::is_valid_extension;
+use crate::conditional;
 use crate::{Entry, Error};
-use axum::response::{IntoResponse, IntoResponseParts};
+use axum::headers::{ETag, IfNoneMatch};
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, IntoResponseParts, Response};
 use axum::{headers, TypedHeader};
 use once_cell::sync::Lazy;
+use std::collections::HashMap;
 use std::io::Cursor;
-use std::time::Duration;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
 use syntect::highlighting::ThemeSet;
 use syntect::html::{css_for_theme_with_class_style, line_tokens_to_classed_spans, ClassStyle};
 use syntect::parsing::{ParseState, ScopeStack, SyntaxSet};
@@ -16,11 +22,24 @@ pub static DATA: Lazy<Data> = Lazy::new(|| {
     let data = include_str!("themes/ayu-dark.tmTheme");
     let dark_theme = ThemeSet::load_from_reader(&mut Cursor::new(data)).unwrap();
 
+    let light = css_for_theme_with_class_style(&light_theme, ClassStyle::Spaced).unwrap();
+    let dark = css_for_theme_with_class_style(&dark_theme, ClassStyle::Spaced).unwrap();
+
+    let mut themes = ThemeSet::new();
+    themes.themes.insert("ayu-light".to_string(), light_theme);
+    themes.themes.insert("ayu-dark".to_string(), dark_theme);
+
+    let main = include_str!("themes/style.css");
+
     Data {
-        main: include_str!("themes/style.css"),
-        light: css_for_theme_with_class_style(&light_theme, ClassStyle::Spaced).unwrap(),
-        dark: css_for_theme_with_class_style(&dark_theme, ClassStyle::Spaced).unwrap(),
+        main,
+        main_etag: conditional::etag(main.as_bytes()),
+        light_etag: conditional::etag(light.as_bytes()),
+        dark_etag: conditional::etag(dark.as_bytes()),
+        light,
+        dark,
         syntax_set: SyntaxSet::load_defaults_newlines(),
+        themes,
     }
 });
 
@@ -28,7 +47,12 @@ pub struct Data<'a> {
     pub main: &'a str,
     pub dark: String,
     pub light: String,
+    pub main_etag: ETag,
+    pub dark_etag: ETag,
+    pub light_etag: ETag,
     pub syntax_set: SyntaxSet,
+    /// Loaded themes by name.
+    pub themes: ThemeSet,
 }
 
 fn common_headers() -> impl IntoResponseParts {
@@ -38,22 +62,147 @@ fn common_headers() -> impl IntoResponseParts {
     )
 }
 
-pub fn main() -> impl IntoResponse {
-    (common_headers(), DATA.main.to_string())
+/// Respond with the stylesheet `css` or just 304 if the client already has the version `etag`.
+fn stylesheet(
+    css: &str,
+    etag: &ETag,
+    if_none_match: &Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    let headers = (common_headers(), TypedHeader(etag.clone()));
+
+    if conditional::not_modified(if_none_match, etag) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    (headers, css.to_string()).into_response()
+}
+
+#[allow(clippy::unused_async)]
+pub async fn main(if_none_match: Option<TypedHeader<IfNoneMatch>>) -> Response {
+    stylesheet(DATA.main, &DATA.main_etag, &if_none_match)
+}
+
+#[allow(clippy::unused_async)]
+pub async fn dark(if_none_match: Option<TypedHeader<IfNoneMatch>>) -> Response {
+    stylesheet(&DATA.dark, &DATA.dark_etag, &if_none_match)
+}
+
+#[allow(clippy::unused_async)]
+pub async fn light(if_none_match: Option<TypedHeader<IfNoneMatch>>) -> Response {
+    stylesheet(&DATA.light, &DATA.light_etag, &if_none_match)
+}
+
+/// Extension of the syntax detected from the first line of `text`, e.g. a shebang or an XML
+/// declaration, falling back to [`guess_extension`].
+pub fn detect_extension(text: &str) -> Option<String> {
+    let first_line = text.lines().next()?;
+
+    DATA.syntax_set
+        .find_syntax_by_first_line(first_line)
+        .and_then(|syntax| syntax.file_extensions.first())
+        .filter(|ext| is_valid_extension(ext))
+        .cloned()
+        .or_else(|| guess_extension(text, first_line).map(ToString::to_string))
+}
+
+/// Recognize shell shebangs and JSON documents that the syntaxes miss. Anything else is left
+/// undetected rather than guessed wrong.
+fn guess_extension(text: &str, first_line: &str) -> Option<&'static str> {
+    if let Some(command) = first_line.strip_prefix("#!") {
+        let mut words = command.split_whitespace();
+        let mut program = words.next()?.rsplit('/').next()?;
+
+        if program == "env" {
+            program = words.find(|word| !word.starts_with('-'))?;
+        }
+
+        return match program {
+            "sh" | "bash" | "dash" | "ksh" | "zsh" => Some("sh"),
+            _ => None,
+        };
+    }
+
+    let text = text.trim_start();
+
+    if (text.starts_with('{') || text.starts_with('['))
+        && serde_json::from_str::<serde::de::IgnoredAny>(text).is_ok()
+    {
+        return Some("json");
+    }
+
+    None
+}
+
+/// Start of the table row of a line, followed by its number and the closing quote of the `id`.
+const ROW_START: &str = r#"<tr id="L"#;
+
+/// Inclusive range of line numbers selected with the `hl` query parameter, e.g. `12` or `12-18`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct LineRange {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl FromStr for LineRange {
+    type Err = ();
+
+    /// Parse a single line number or a range of two. Line numbers start at one and ranges must not
+    /// be reversed.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (start, end) = s.split_once('-').unwrap_or((s, s));
+        let start = start.trim().parse::<usize>().map_err(|_| ())?;
+        let end = end.trim().parse::<usize>().map_err(|_| ())?;
+
+        if start == 0 || end < start {
+            return Err(());
+        }
+
+        Ok(Self { start, end })
+    }
 }
 
-pub fn dark() -> impl IntoResponse {
-    (common_headers(), DATA.dark.clone())
+impl LineRange {
+    fn contains(self, line: usize) -> bool {
+        (self.start..=self.end).contains(&line)
+    }
 }
 
-pub fn light() -> impl IntoResponse {
-    (common_headers(), DATA.light.clone())
+/// Add the `highlighted` class to the rows of `html` generated by [`highlight`] whose lines are
+/// within `range`. Lines beyond the end of the paste are ignored.
+pub fn mark_lines(html: &str, range: LineRange) -> String {
+    let mut marked = String::with_capacity(html.len());
+    let mut rest = html;
+
+    while let Some(start) = rest.find(ROW_START) {
+        let number_start = start + ROW_START.len();
+        let number_end = match rest[number_start..].find('"') {
+            Some(len) => number_start + len,
+            None => break,
+        };
+
+        marked.push_str(&rest[..=number_end]);
+
+        if rest[number_start..number_end]
+            .parse()
+            .is_ok_and(|line| range.contains(line))
+        {
+            marked.push_str(r#" class="highlighted""#);
+        }
+
+        rest = &rest[number_end + 1..];
+    }
+
+    marked.push_str(rest);
+    marked
 }
 
+/// Highlight `entry` with the syntax matching the extension or name in `ext`. Every line of the
+/// text becomes one table row. Lines are parsed with their endings as the syntax set expects, but
+/// the endings are left out of the rows so that no blank lines are rendered in between.
 pub fn highlight(entry: &Entry, ext: &str) -> Result<String, Error> {
     let syntax_ref = DATA
         .syntax_set
-        .find_syntax_by_extension(ext)
+        .find_syntax_by_token(ext)
         .unwrap_or_else(|| DATA.syntax_set.find_syntax_by_extension("txt").unwrap());
 
     let mut parse_state = ParseState::new(syntax_ref);
@@ -71,9 +220,10 @@ pub fn highlight(entry: &Entry, ext: &str) -> Result<String, Error> {
         .unwrap();
 
         line_number += 1;
-        let formatted_str = formatted.as_str();
-        let line_number =
-            format!(r#"<td class="line-number"><a href=#L{line_number}>{line_number:>4}</a></td>"#);
+        let formatted_str = formatted.replace("\r\n", "").replace('\n', "");
+        let line_number = format!(
+            r#"{ROW_START}{line_number}"><td class="line-number"><a href=#L{line_number}>{line_number:>4}</a></td>"#
+        );
         html.push_str(&line_number);
 
         let line = format!(r#"<td class="line">{formatted_str}"#);
@@ -86,3 +236,209 @@ pub fn highlight(entry: &Entry, ext: &str) -> Result<String, Error> {
 
     Ok(html)
 }
+
+/// Failure state of a single syntax.
+#[derive(Default)]
+struct BreakerState {
+    failures: u32,
+    disabled_until: Option<Instant>,
+}
+
+/// Circuit breaker that disables syntaxes whose highlighting fails repeatedly.
+#[derive(Default)]
+pub struct Breaker {
+    states: HashMap<String, BreakerState>,
+}
+
+impl Breaker {
+    /// Return `true` if `syntax` is disabled at `now`. Syntaxes are enabled again once their
+    /// cooldown has passed.
+    pub fn is_disabled(&mut self, syntax: &str, now: Instant) -> bool {
+        match self
+            .states
+            .get(syntax)
+            .and_then(|state| state.disabled_until)
+        {
+            Some(until) if now < until => true,
+            Some(_) => {
+                tracing::info!(syntax, "re-enabling highlighting");
+                self.states.remove(syntax);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Record a failed or timed out highlight of `syntax` and disable it for `cooldown` after
+    /// `threshold` consecutive failures.
+    pub fn record_failure(
+        &mut self,
+        syntax: &str,
+        threshold: u32,
+        cooldown: Duration,
+        now: Instant,
+    ) {
+        if threshold == 0 {
+            return;
+        }
+
+        let state = self.states.entry(syntax.to_string()).or_default();
+        state.failures += 1;
+
+        if state.failures >= threshold && state.disabled_until.is_none() {
+            tracing::warn!(
+                syntax,
+                failures = state.failures,
+                "disabling highlighting for {cooldown:?}"
+            );
+            state.disabled_until = Some(now + cooldown);
+        }
+    }
+
+    /// Record a successful highlight of `syntax`, resetting its failure count.
+    pub fn record_success(&mut self, syntax: &str) {
+        self.states.remove(syntax);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn detect_extensions() {
+        assert_eq!(
+            detect_extension("#!/usr/bin/env python3\nprint(1)").as_deref(),
+            Some("py")
+        );
+        assert_eq!(detect_extension("#!/bin/bash\necho").as_deref(), Some("sh"));
+        assert_eq!(detect_extension("hello world"), None);
+        assert_eq!(detect_extension(""), None);
+
+        assert_eq!(
+            detect_extension("{\n  \"foo\": [1, 2]\n}").as_deref(),
+            Some("json")
+        );
+        assert_eq!(detect_extension("[1, 2, 3]").as_deref(), Some("json"));
+        assert_eq!(detect_extension("[section]\nkey=value"), None);
+        assert_eq!(detect_extension("{ not json"), None);
+        assert_eq!(
+            detect_extension("#!/usr/bin/env -S zsh -f\necho").as_deref(),
+            Some("sh")
+        );
+        assert_eq!(
+            detect_extension("#!/opt/bin/dash\necho").as_deref(),
+            Some("sh")
+        );
+        assert_eq!(detect_extension("#!/usr/bin/unknown\n"), None);
+    }
+
+    #[test]
+    fn parse_line_ranges() {
+        assert_eq!("12".parse(), Ok(LineRange { start: 12, end: 12 }));
+        assert_eq!("12-18".parse(), Ok(LineRange { start: 12, end: 18 }));
+        assert_eq!(" 3 - 4 ".parse(), Ok(LineRange { start: 3, end: 4 }));
+        assert!("abc".parse::<LineRange>().is_err());
+        assert!("".parse::<LineRange>().is_err());
+        assert!("0".parse::<LineRange>().is_err());
+        assert!("18-12".parse::<LineRange>().is_err());
+        assert!("1-2-3".parse::<LineRange>().is_err());
+        assert!("-5".parse::<LineRange>().is_err());
+    }
+
+    #[test]
+    fn mark_line_range() -> Result<(), Error> {
+        let entry = Entry {
+            text: "a\nb\nc\nd".to_string(),
+            ..Default::default()
+        };
+
+        let html = highlight(&entry, "txt")?;
+        let marked = mark_lines(&html, LineRange { start: 2, end: 3 });
+        assert_eq!(marked.matches(r#" class="highlighted""#).count(), 2);
+        assert!(marked.contains(r#"<tr id="L2" class="highlighted">"#));
+        assert!(marked.contains(r#"<tr id="L3" class="highlighted">"#));
+        assert!(marked.contains(r#"<tr id="L4">"#));
+
+        let marked = mark_lines(
+            &html,
+            LineRange {
+                start: 9999,
+                end: 10000,
+            },
+        );
+        assert_eq!(marked, html);
+
+        Ok(())
+    }
+
+    #[test]
+    fn rows_match_lines() -> Result<(), Error> {
+        for text in [
+            "",
+            "foo",
+            "foo\n",
+            "foo\nbar",
+            "foo\nbar\n",
+            "foo\r\nbar\r\n",
+            "\n\n",
+        ] {
+            let entry = Entry {
+                text: text.to_string(),
+                ..Default::default()
+            };
+
+            let html = highlight(&entry, "txt")?;
+            assert_eq!(
+                html.matches(ROW_START).count(),
+                text.lines().count(),
+                "{text:?}"
+            );
+            assert_eq!(
+                html.matches("</tr>").count(),
+                text.lines().count(),
+                "{text:?}"
+            );
+            assert!(!html.contains('\n'), "{text:?}");
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn repeated_failures_trip_breaker() {
+        let mut breaker = Breaker::default();
+        let cooldown = Duration::from_secs(60);
+        let now = Instant::now();
+
+        breaker.record_failure("rs", 3, cooldown, now);
+        breaker.record_failure("rs", 3, cooldown, now);
+        assert!(!breaker.is_disabled("rs", now));
+
+        breaker.record_success("rs");
+        breaker.record_failure("rs", 3, cooldown, now);
+        breaker.record_failure("rs", 3, cooldown, now);
+        assert!(!breaker.is_disabled("rs", now));
+
+        breaker.record_failure("rs", 3, cooldown, now);
+        assert!(breaker.is_disabled("rs", now));
+        assert!(breaker.is_disabled("rs", now + Duration::from_secs(59)));
+        assert!(!breaker.is_disabled("py", now));
+
+        assert!(!breaker.is_disabled("rs", now + cooldown));
+        breaker.record_failure("rs", 3, cooldown, now + cooldown);
+        assert!(!breaker.is_disabled("rs", now + cooldown));
+    }
+
+    #[test]
+    fn zero_threshold_never_trips() {
+        let mut breaker = Breaker::default();
+        let now = Instant::now();
+
+        for _ in 0..10 {
+            breaker.record_failure("rs", 0, Duration::from_secs(60), now);
+        }
+
+        assert!(!breaker.is_disabled("rs", now));
+    }
+}
@@ -0,0 +1,270 @@
+//! HTTP/3 server on the UDP port of the TLS listener, enabled with the `http3` feature and
+//! `WASTEBIN_HTTP3`. Clients discover it through the `Alt-Svc` header of HTTP/1.1 and HTTP/2
+//! responses and fall back to those if QUIC is blocked.
+
+use crate::config::Tls;
+use axum::extract::ConnectInfo;
+use axum::http::header::ALT_SVC;
+use axum::http::{HeaderValue, Request, Response, StatusCode};
+use bytes::{Buf, BufMut, Bytes, BytesMut};
+use h3::server::RequestStream;
+use http_body::Body;
+use hyper::service::Service;
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use tower_http::set_header::SetResponseHeaderLayer;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, io::Error),
+    #[error("no private key found in {0}")]
+    NoKey(PathBuf),
+    #[error("tls error: {0}")]
+    Tls(#[from] rustls::Error),
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+}
+
+/// Layer advertising HTTP/3 on `port` to clients connected via TCP.
+pub fn alt_svc(port: u16) -> SetResponseHeaderLayer<HeaderValue> {
+    let value =
+        HeaderValue::from_str(&format!("h3=\":{port}\"; ma=86400")).expect("valid header value");
+    SetResponseHeaderLayer::if_not_present(ALT_SVC, value)
+}
+
+fn open(path: &Path) -> Result<BufReader<File>, Error> {
+    File::open(path)
+        .map(BufReader::new)
+        .map_err(|err| Error::Read(path.to_path_buf(), err))
+}
+
+/// QUIC configuration with the certificate chain and private key of `tls`.
+fn server_config(tls: &Tls) -> Result<quinn::ServerConfig, Error> {
+    let certs = rustls_pemfile::certs(&mut open(&tls.cert)?)
+        .map_err(|err| Error::Read(tls.cert.clone(), err))?
+        .into_iter()
+        .map(rustls::Certificate)
+        .collect();
+
+    let mut reader = open(&tls.key)?;
+    let key = loop {
+        match rustls_pemfile::read_one(&mut reader)
+            .map_err(|err| Error::Read(tls.key.clone(), err))?
+        {
+            Some(
+                rustls_pemfile::Item::PKCS8Key(key)
+                | rustls_pemfile::Item::RSAKey(key)
+                | rustls_pemfile::Item::ECKey(key),
+            ) => break rustls::PrivateKey(key),
+            Some(_) => continue,
+            None => return Err(Error::NoKey(tls.key.clone())),
+        }
+    };
+
+    let mut crypto = rustls::ServerConfig::builder()
+        .with_safe_defaults()
+        .with_no_client_auth()
+        .with_single_cert(certs, key)?;
+    crypto.alpn_protocols = vec![b"h3".to_vec()];
+
+    Ok(quinn::ServerConfig::with_crypto(Arc::new(crypto)))
+}
+
+/// Serve `router` over HTTP/3 on the UDP port `addr` until the endpoint fails. Request bodies are
+/// buffered and rejected above `max_request_size` bytes.
+pub async fn serve(
+    addr: SocketAddr,
+    tls: &Tls,
+    router: axum::Router,
+    max_request_size: usize,
+) -> Result<(), Error> {
+    let endpoint = quinn::Endpoint::server(server_config(tls)?, addr)?;
+    accept(endpoint, router, max_request_size).await;
+    Ok(())
+}
+
+async fn accept(endpoint: quinn::Endpoint, router: axum::Router, max_request_size: usize) {
+    while let Some(connecting) = endpoint.accept().await {
+        let router = router.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = connection(connecting, router, max_request_size).await {
+                tracing::debug!("http3 connection failed: {err}");
+            }
+        });
+    }
+}
+
+async fn connection(
+    connecting: quinn::Connecting,
+    router: axum::Router,
+    max_request_size: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+    let conn = connecting.await?;
+    let remote = conn.remote_address();
+    let mut conn = h3::server::Connection::new(h3_quinn::Connection::new(conn)).await?;
+
+    while let Some((req, stream)) = conn.accept().await? {
+        let router = router.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = request(req, stream, router, remote, max_request_size).await {
+                tracing::debug!("http3 request failed: {err}");
+            }
+        });
+    }
+
+    Ok(())
+}
+
+/// Collect the body of `req` up to `max_request_size` bytes, pass it to `router` and stream the
+/// response back.
+async fn request<S>(
+    req: Request<()>,
+    mut stream: RequestStream<S, Bytes>,
+    mut router: axum::Router,
+    remote: SocketAddr,
+    max_request_size: usize,
+) -> Result<(), Box<dyn std::error::Error + Send + Sync>>
+where
+    S: h3::quic::BidiStream<Bytes>,
+{
+    let mut body = BytesMut::new();
+
+    while let Some(chunk) = stream.recv_data().await? {
+        if body.len() + chunk.remaining() > max_request_size {
+            let res = Response::builder()
+                .status(StatusCode::PAYLOAD_TOO_LARGE)
+                .body(())?;
+            stream.send_response(res).await?;
+            return Ok(stream.finish().await?);
+        }
+
+        body.put(chunk);
+    }
+
+    let (parts, ()) = req.into_parts();
+    let mut req = Request::from_parts(parts, hyper::Body::from(body.freeze()));
+    req.extensions_mut().insert(ConnectInfo(remote));
+
+    let res = match router.call(req).await {
+        Ok(res) => res,
+        Err(err) => match err {},
+    };
+
+    let (parts, mut body) = res.into_parts();
+    stream
+        .send_response(Response::from_parts(parts, ()))
+        .await?;
+
+    while let Some(data) = body.data().await {
+        stream.send_data(data?).await?;
+    }
+
+    stream.finish().await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_helpers::make_app;
+    use axum::http::Method;
+
+    /// Send a request with `body` on `send` and return the response with its body.
+    async fn fetch(
+        send: &mut h3::client::SendRequest<h3_quinn::OpenStreams, Bytes>,
+        req: Request<()>,
+        body: Bytes,
+    ) -> Result<(Response<()>, Vec<u8>), Box<dyn std::error::Error + Send + Sync>> {
+        let mut stream = send.send_request(req).await?;
+        if !body.is_empty() {
+            stream.send_data(body).await?;
+        }
+        stream.finish().await?;
+
+        let res = stream.recv_response().await?;
+        let mut data = Vec::new();
+        while let Some(chunk) = stream.recv_data().await? {
+            data.put(chunk);
+        }
+
+        Ok((res, data))
+    }
+
+    #[tokio::test]
+    async fn create_and_fetch_paste() -> Result<(), Box<dyn std::error::Error + Send + Sync>> {
+        let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()])?;
+        let dir = std::env::temp_dir().join(format!("wastebin-http3-{}", std::process::id()));
+        std::fs::create_dir_all(&dir)?;
+        std::fs::write(dir.join("cert.pem"), cert.serialize_pem()?)?;
+        std::fs::write(dir.join("key.pem"), cert.serialize_private_key_pem())?;
+
+        let tls = Tls {
+            cert: dir.join("cert.pem"),
+            key: dir.join("key.pem"),
+            http3: true,
+        };
+        let config = server_config(&tls);
+        std::fs::remove_dir_all(&dir)?;
+
+        let endpoint = quinn::Endpoint::server(config?, "127.0.0.1:0".parse()?)?;
+        let addr = endpoint.local_addr()?;
+        let app = make_app().unwrap().layer(alt_svc(addr.port()));
+        tokio::spawn(accept(endpoint, app, 1024));
+
+        let mut roots = rustls::RootCertStore::empty();
+        roots.add(&rustls::Certificate(cert.serialize_der()?))?;
+        let mut crypto = rustls::ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        crypto.alpn_protocols = vec![b"h3".to_vec()];
+
+        let mut client = quinn::Endpoint::client("127.0.0.1:0".parse()?)?;
+        client.set_default_client_config(quinn::ClientConfig::new(Arc::new(crypto)));
+        let conn = client.connect(addr, "localhost")?.await?;
+        let (mut driver, mut send) = h3::client::new(h3_quinn::Connection::new(conn)).await?;
+        tokio::spawn(async move { std::future::poll_fn(|cx| driver.poll_close(cx)).await });
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("https://localhost/api/entries")
+            .header("content-type", "application/json")
+            .body(())?;
+        let entry = crate::Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+        let (res, body) = fetch(&mut send, req, serde_json::to_vec(&entry)?.into()).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload: serde_json::Value = serde_json::from_slice(&body)?;
+        let path = payload["path"].as_str().unwrap();
+
+        let req = Request::builder()
+            .uri(format!("https://localhost/api/entries{path}"))
+            .body(())?;
+        let (res, body) = fetch(&mut send, req, Bytes::new()).await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get(ALT_SVC).unwrap(),
+            &format!("h3=\":{}\"; ma=86400", addr.port())
+        );
+        assert_eq!(body, b"FooBarBaz");
+
+        let req = Request::builder()
+            .method(Method::POST)
+            .uri("https://localhost/api/entries")
+            .header("content-type", "application/json")
+            .body(())?;
+        let (res, _) = fetch(&mut send, req, Bytes::from(vec![b' '; 2048])).await?;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        Ok(())
+    }
+}
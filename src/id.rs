@@ -1,7 +1,24 @@
-use crate::{Entry, Error};
+use crate::Error;
+use axum::http::header::{self, HeaderMap, HeaderValue};
+use rand::Rng;
 use std::convert::{From, TryFrom};
 use std::fmt;
 
+/// URLs of the representations of a paste, prefixed with the base path.
+#[derive(Clone, Debug)]
+pub struct Urls {
+    /// HTML page.
+    pub page: String,
+    /// Raw text.
+    pub raw: String,
+    /// Download of the raw text.
+    pub download: String,
+    /// Deletion metadata.
+    pub info: String,
+    /// Deletion link of the web interface.
+    pub delete: String,
+}
+
 #[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 pub struct Id {
     n: u32,
@@ -11,28 +28,86 @@ impl fmt::Display for Id {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
         let mut s = String::with_capacity(6);
 
-        s.push(CHAR_TABLE[(((self.n >> 26) & 0x3f) as usize)]);
-        s.push(CHAR_TABLE[(((self.n >> 20) & 0x3f) as usize)]);
-        s.push(CHAR_TABLE[(((self.n >> 14) & 0x3f) as usize)]);
-        s.push(CHAR_TABLE[(((self.n >> 8) & 0x3f) as usize)]);
-        s.push(CHAR_TABLE[(((self.n >> 2) & 0x3f) as usize)]);
-        s.push(CHAR_TABLE[((self.n & 0x3) as usize)]);
+        s.push(CHAR_TABLE[((self.n >> 26) & 0x3f) as usize]);
+        s.push(CHAR_TABLE[((self.n >> 20) & 0x3f) as usize]);
+        s.push(CHAR_TABLE[((self.n >> 14) & 0x3f) as usize]);
+        s.push(CHAR_TABLE[((self.n >> 8) & 0x3f) as usize]);
+        s.push(CHAR_TABLE[((self.n >> 2) & 0x3f) as usize]);
+        s.push(CHAR_TABLE[(self.n & 0x3) as usize]);
 
         write!(f, "{s}")
     }
 }
 
 impl Id {
+    /// Generate a random identifier.
+    pub async fn random() -> Result<Self, Error> {
+        let n = tokio::task::spawn_blocking(|| {
+            let mut rng = rand::thread_rng();
+            rng.gen::<u32>()
+        })
+        .await?;
+
+        Ok(Self::from(n))
+    }
+
     pub fn as_u32(self) -> u32 {
         self.n
     }
 
-    pub fn to_url_path(self, entry: &Entry) -> String {
-        match entry.extension {
-            Some(ref ext) => format!("/{}.{}", self, ext),
-            None => format!("/{}", self),
+    /// Parse a client requested `slug`, which must be the canonical string form of an `Id`.
+    pub fn from_slug(slug: &str) -> Result<Self, Error> {
+        let id = Self::try_from(slug)?;
+
+        if id.to_string() == slug {
+            Ok(id)
+        } else {
+            Err(Error::IllegalCharacters)
+        }
+    }
+
+    /// URL of the paste page with an optional `extension` below the `base` path.
+    pub fn page(self, base: &str, extension: Option<&str>) -> String {
+        match extension {
+            Some(ext) => format!("{base}/{self}.{ext}"),
+            None => format!("{base}/{self}"),
+        }
+    }
+
+    /// URLs of the paste with `extension` below the `base` path.
+    pub fn urls(self, base: &str, extension: &str) -> Urls {
+        Urls {
+            page: self.page(base, Some(extension)),
+            raw: format!("{base}/api/entries/{self}"),
+            download: format!("{base}/download/{self}/{extension}"),
+            info: format!("{base}/api/entries/{self}/info"),
+            delete: format!("{base}/delete/{self}"),
         }
     }
+
+    /// `Link` header listing the representations of the paste with `extension` below the `base`
+    /// path. Empty if the extension cannot be part of a header.
+    pub fn links(self, base: &str, extension: &str) -> HeaderMap {
+        let Urls {
+            page,
+            raw,
+            download,
+            info,
+            ..
+        } = self.urls(base, extension);
+
+        let links = format!(
+            r#"<{page}>; rel="canonical"; type="text/html", <{raw}>; rel="alternate"; type="text/plain", <{download}>; rel="enclosure", <{info}>; rel="describedby"; type="application/json""#
+        );
+
+        let mut headers = HeaderMap::new();
+
+        if let Ok(value) = HeaderValue::from_str(&links) {
+            headers.insert(header::LINK, value);
+        }
+
+        headers
+    }
 }
 
 static CHAR_TABLE: &[char; 64] = &[
@@ -105,4 +180,22 @@ mod tests {
         assert!(Id::try_from("abDE+-1").is_err());
         assert!(Id::try_from("abDE+").is_err());
     }
+
+    #[test]
+    fn convert_id_from_slug() {
+        assert!(Id::from_slug("wastea").is_ok());
+        assert!(Id::from_slug("wastez").is_err());
+        assert!(Id::from_slug("waste").is_err());
+    }
+
+    #[test]
+    fn links() {
+        let headers = Id::from(0).links("", "rs");
+        let links = headers.get(header::LINK).unwrap().to_str().unwrap();
+        assert!(links.contains(r#"</aaaaaa.rs>; rel="canonical""#));
+        assert!(links.contains(r#"</api/entries/aaaaaa>; rel="alternate""#));
+        assert!(links.contains(r#"</download/aaaaaa/rs>; rel="enclosure""#));
+
+        assert!(Id::from(0).links("", "r\ns").is_empty());
+    }
 }
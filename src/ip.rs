@@ -0,0 +1,102 @@
+use crate::config::Config;
+use axum::async_trait;
+use axum::extract::{ConnectInfo, FromRequest, RequestParts};
+use axum::http::HeaderMap;
+use std::convert::Infallible;
+use std::net::{IpAddr, Ipv4Addr, SocketAddr};
+use std::sync::Arc;
+
+/// Address of the client that issued a request. Falls back to the unspecified address if the
+/// peer is unknown.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ClientIp(pub IpAddr);
+
+/// Parse a single `X-Forwarded-For` entry which may contain a port.
+fn parse_entry(entry: &str) -> Option<IpAddr> {
+    entry
+        .parse::<IpAddr>()
+        .ok()
+        .or_else(|| entry.parse::<SocketAddr>().ok().map(|addr| addr.ip()))
+}
+
+/// Collect all `X-Forwarded-For` entries in order, ignoring unparsable ones.
+fn forwarded_for(headers: &HeaderMap) -> Vec<IpAddr> {
+    headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|entry| parse_entry(entry.trim()))
+        .collect()
+}
+
+/// Resolve the client address from the `peer` address and the `forwarded` chain. The peer counts
+/// as the first of `trusted_hops` proxies, each further hop skips one trailing entry of the
+/// chain. With zero trusted hops the chain is ignored entirely.
+fn resolve(peer: Option<IpAddr>, forwarded: &[IpAddr], trusted_hops: usize) -> Option<IpAddr> {
+    if trusted_hops == 0 || forwarded.is_empty() {
+        return peer;
+    }
+
+    Some(forwarded[forwarded.len().saturating_sub(trusted_hops)])
+}
+
+#[async_trait]
+impl<B: Send> FromRequest<B> for ClientIp {
+    type Rejection = Infallible;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let peer = req
+            .extensions()
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip());
+
+        let trusted_hops = req
+            .extensions()
+            .get::<Arc<Config>>()
+            .map_or(0, |config| config.xff_trusted_hops);
+
+        let ip = resolve(peer, &forwarded_for(req.headers()), trusted_hops)
+            .unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED));
+
+        Ok(Self(ip))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn parse_forwarded_chain() {
+        let mut headers = HeaderMap::new();
+        headers.append(
+            "x-forwarded-for",
+            HeaderValue::from_static("1.1.1.1, garbage, 2.2.2.2:8080"),
+        );
+        headers.append("x-forwarded-for", HeaderValue::from_static("[::1]:80"));
+
+        assert_eq!(
+            forwarded_for(&headers),
+            vec![ip("1.1.1.1"), ip("2.2.2.2"), ip("::1")]
+        );
+    }
+
+    #[test]
+    fn resolve_multi_hop_chain() {
+        let peer = Some(ip("10.0.0.1"));
+        let chain = [ip("1.1.1.1"), ip("2.2.2.2"), ip("3.3.3.3")];
+
+        assert_eq!(resolve(peer, &chain, 0), peer);
+        assert_eq!(resolve(peer, &chain, 1), Some(ip("3.3.3.3")));
+        assert_eq!(resolve(peer, &chain, 2), Some(ip("2.2.2.2")));
+        assert_eq!(resolve(peer, &chain, 3), Some(ip("1.1.1.1")));
+        assert_eq!(resolve(peer, &chain, 10), Some(ip("1.1.1.1")));
+        assert_eq!(resolve(peer, &[], 2), peer);
+    }
+}
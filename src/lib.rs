@@ -0,0 +1,458 @@
+use crate::config::{is_disallowed_control, Config, ControlCharacters};
+use axum::http::StatusCode;
+use axum::{middleware, Extension};
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::net::IpAddr;
+use std::num::TryFromIntError;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Semaphore;
+use tower_http::compression::predicate::{NotForContentType, Predicate, SizeAbove};
+use tower_http::compression::CompressionLayer;
+use tower_http::limit::RequestBodyLimitLayer;
+use tower_http::timeout::TimeoutLayer;
+use tower_http::trace::TraceLayer;
+
+mod base;
+pub mod cache;
+#[cfg(feature = "client")]
+pub mod client;
+mod conditional;
+pub mod config;
+mod crypto;
+mod csp;
+pub mod db;
+mod decompress;
+mod highlight;
+#[cfg(feature = "http3")]
+pub mod http3;
+mod id;
+mod ip;
+mod limit;
+mod metrics;
+mod password;
+mod rest;
+#[cfg(test)]
+mod test_helpers;
+mod web;
+
+#[derive(thiserror::Error, Debug)]
+pub enum Error {
+    #[error("axum http error: {0}")]
+    Axum(#[from] axum::http::Error),
+    #[error("deletion time expired")]
+    DeletionTimeExpired,
+    #[error("sqlite error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[error("migrations error: {0}")]
+    Migration(#[from] rusqlite_migration::Error),
+    #[error("database schema version {0} is newer than the supported version {1}")]
+    SchemaTooNew(usize, usize),
+    #[error("wrong size")]
+    WrongSize,
+    #[error("paste exceeds the maximum size of {0} bytes")]
+    PasteTooLarge(usize),
+    #[error("paste exceeds the maximum of {0} lines")]
+    TooManyLines(usize),
+    #[error("illegal characters")]
+    IllegalCharacters,
+    #[error("id is already taken")]
+    IdTaken,
+    #[error("extension not allowed")]
+    ExtensionNotAllowed,
+    #[error("invalid extension")]
+    InvalidExtension,
+    #[error("at most {0} pastes can be fetched at once")]
+    TooManyIds(usize),
+    #[error("at most {0} pastes per client are allowed")]
+    QuotaExceeded(usize),
+    #[error("too many pastes, at most {0} per minute are allowed")]
+    RateLimited(usize),
+    #[error("the maximum of {0} pastes has been reached")]
+    AtCapacity(usize),
+    #[error("file name exceeds the maximum of {0} characters")]
+    FilenameTooLong(usize),
+    #[error("unauthorized")]
+    Unauthorized,
+    #[error("not found")]
+    NotFound,
+    #[error("integer conversion error: {0}")]
+    IntConversion(#[from] TryFromIntError),
+    #[error("join error: {0}")]
+    Join(#[from] tokio::task::JoinError),
+    #[error("syntax highlighting error: {0}")]
+    SyntaxHighlighting(#[from] syntect::Error),
+    #[error("syntax parsing error: {0}")]
+    SyntaxParsing(#[from] syntect::parsing::ParsingError),
+    #[error("time formatting error: {0}")]
+    TimeFormatting(#[from] time::error::Format),
+    #[error("highlighting timed out")]
+    HighlightTimeout,
+    #[error("archive error: {0}")]
+    Archive(#[from] zip::result::ZipError),
+    #[error("encryption error: {0}")]
+    Encryption(String),
+    #[error("compression error: {0}")]
+    Compression(std::io::Error),
+    #[error("password protected pastes cannot be appendable")]
+    ProtectedAppend,
+}
+
+#[derive(Clone, Default, Serialize, Deserialize)]
+pub struct Entry {
+    /// Content
+    pub text: String,
+    /// File extension
+    pub extension: Option<String>,
+    /// Expiration in seconds from now, remaining seconds for stored entries
+    pub expires: Option<u32>,
+    /// Delete if read
+    pub burn_after_reading: Option<bool>,
+    /// Seconds since creation
+    pub seconds_since_creation: u32,
+    /// Requested identifier instead of a random one
+    pub slug: Option<String>,
+    /// Issue a token that allows appending to the paste later on
+    pub appendable: Option<bool>,
+    /// Token required to append to the paste
+    #[serde(skip)]
+    pub append_token: Option<String>,
+    /// Original file name
+    pub filename: Option<String>,
+    /// Address of the client that created the paste, only recorded to enforce quotas
+    #[serde(skip)]
+    pub client_ip: Option<IpAddr>,
+    /// Password required to read the paste
+    pub password: Option<String>,
+    /// Text encrypted with the password, `text` is empty while it is set
+    #[serde(skip)]
+    pub ciphertext: Option<Vec<u8>>,
+}
+
+impl fmt::Debug for Entry {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Entry")
+            .field("text", &format_args!("<{} bytes>", self.text.len()))
+            .field("extension", &self.extension)
+            .field("expires", &self.expires)
+            .field("burn_after_reading", &self.burn_after_reading)
+            .field("seconds_since_creation", &self.seconds_since_creation)
+            .field("slug", &self.slug)
+            .field("appendable", &self.appendable)
+            .field(
+                "append_token",
+                &self
+                    .append_token
+                    .as_ref()
+                    .map(|_| format_args!("<redacted>")),
+            )
+            .field("filename", &self.filename)
+            .field("client_ip", &self.client_ip)
+            .field(
+                "password",
+                &self.password.as_ref().map(|_| format_args!("<redacted>")),
+            )
+            .field(
+                "ciphertext",
+                &self.ciphertext.as_ref().map(|_| format_args!("<redacted>")),
+            )
+            .finish()
+    }
+}
+
+/// Paste text in log lines, redacted unless content logging is enabled in the config.
+pub struct LogContent<'a> {
+    text: &'a str,
+    verbose: bool,
+}
+
+impl<'a> LogContent<'a> {
+    pub fn new(text: &'a str, config: &Config) -> Self {
+        Self {
+            text,
+            verbose: config.log_content,
+        }
+    }
+}
+
+impl fmt::Display for LogContent<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.verbose {
+            write!(f, "{:?}", self.text)
+        } else {
+            write!(f, "<{} bytes redacted>", self.text.len())
+        }
+    }
+}
+
+/// Seconds left until a paste created `seconds_since_creation` ago can no longer be deleted with
+/// a deletion `window` in seconds or `None` if that time has passed.
+pub fn deletion_time_remaining(seconds_since_creation: u32, window: u64) -> Option<u64> {
+    window
+        .checked_sub(u64::from(seconds_since_creation))
+        .filter(|secs| *secs > 0)
+}
+
+impl Entry {
+    /// Normalize the text according to `config` before it is stored.
+    pub fn normalize(&mut self, config: &Config) {
+        self.extension = self.extension.take().filter(|ext| !ext.is_empty());
+        self.password = self.password.take().filter(|password| !password.is_empty());
+        self.filename = self
+            .filename
+            .take()
+            .and_then(|name| sanitize_filename(&name, config.max_filename_length));
+        config.control_characters.apply(&mut self.text);
+        config.trailing_newline.apply(&mut self.text);
+
+        if config.auto_extension && self.extension.is_none() {
+            self.extension = highlight::detect_extension(&self.text);
+        }
+    }
+
+    /// Check that the entry can be stored with the given `config`.
+    pub fn validate(&self, config: &Config) -> Result<(), Error> {
+        if let Some(ext) = &self.extension {
+            if ext.len() > config.max_extension_length || !cache::is_valid_extension(ext) {
+                return Err(Error::InvalidExtension);
+            }
+        }
+
+        if self.password.is_some() && self.appendable.unwrap_or(false) {
+            return Err(Error::ProtectedAppend);
+        }
+
+        validate_text(&self.text, config)
+    }
+}
+
+/// Reduce `name` to the last path component of at most `max_len` bytes, replacing characters that
+/// are not printable ASCII or would break a `Content-Disposition` header with `_`. Overlong names
+/// are shortened before their extension, if any. Returns `None` if nothing is left.
+pub fn sanitize_filename(name: &str, max_len: usize) -> Option<String> {
+    let name = name.rsplit(['/', '\\']).next().unwrap_or_default().trim();
+
+    let mut sanitized: String = name
+        .chars()
+        .map(|c| {
+            if (c.is_ascii_graphic() && c != '"') || c == ' ' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+
+    if sanitized.len() > max_len {
+        match sanitized
+            .rfind('.')
+            .filter(|&dot| dot > 0 && sanitized.len() - dot < max_len)
+        {
+            Some(dot) => {
+                let extension = sanitized.split_off(dot);
+                sanitized.truncate(max_len - extension.len());
+                sanitized.push_str(&extension);
+            }
+            None => sanitized.truncate(max_len),
+        }
+    }
+
+    if sanitized.is_empty() || sanitized == "." || sanitized == ".." {
+        None
+    } else {
+        Some(sanitized)
+    }
+}
+
+/// Check that `text` can be stored with the given `config`. Sizes are measured in UTF-8 encoded
+/// bytes, not characters.
+pub fn validate_text(text: &str, config: &Config) -> Result<(), Error> {
+    if text.len() > config.max_body_size {
+        return Err(Error::PasteTooLarge(config.max_body_size));
+    }
+
+    if let Some(max_lines) = config.max_lines {
+        if text.lines().count() > max_lines {
+            return Err(Error::TooManyLines(max_lines));
+        }
+    }
+
+    if config.control_characters == ControlCharacters::Reject
+        && text.chars().any(is_disallowed_control)
+    {
+        return Err(Error::IllegalCharacters);
+    }
+
+    Ok(())
+}
+
+pub type Router = axum::Router<http_body::Limited<axum::body::Body>>;
+
+impl From<Error> for StatusCode {
+    fn from(err: Error) -> Self {
+        match err {
+            Error::Sqlite(err) => match err {
+                rusqlite::Error::QueryReturnedNoRows => StatusCode::NOT_FOUND,
+                _ => StatusCode::INTERNAL_SERVER_ERROR,
+            },
+            Error::IllegalCharacters
+            | Error::WrongSize
+            | Error::TooManyLines(_)
+            | Error::ExtensionNotAllowed
+            | Error::InvalidExtension
+            | Error::TooManyIds(_)
+            | Error::FilenameTooLong(_)
+            | Error::ProtectedAppend
+            | Error::DeletionTimeExpired => StatusCode::BAD_REQUEST,
+            Error::PasteTooLarge(_) => StatusCode::PAYLOAD_TOO_LARGE,
+            Error::IdTaken => StatusCode::CONFLICT,
+            Error::QuotaExceeded(_) | Error::RateLimited(_) => StatusCode::TOO_MANY_REQUESTS,
+            Error::Unauthorized => StatusCode::UNAUTHORIZED,
+            Error::NotFound => StatusCode::NOT_FOUND,
+            Error::HighlightTimeout | Error::AtCapacity(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Error::Join(_)
+            | Error::IntConversion(_)
+            | Error::TimeFormatting(_)
+            | Error::Migration(_)
+            | Error::SchemaTooNew(..)
+            | Error::SyntaxHighlighting(_)
+            | Error::SyntaxParsing(_)
+            | Error::Archive(_)
+            | Error::Encryption(_)
+            | Error::Compression(_)
+            | Error::Axum(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
+impl Error {
+    /// Split into status code and message for clients. Server errors are logged and replaced by
+    /// a generic message so that their details never end up in responses.
+    pub fn into_response_parts(self) -> (StatusCode, String) {
+        let message = self.to_string();
+        let status = StatusCode::from(self);
+
+        if status.is_server_error() {
+            tracing::error!(%status, "{message}");
+            (status, "internal server error".to_string())
+        } else {
+            (status, message)
+        }
+    }
+}
+
+pub fn make_app(cache_layer: cache::Layer, config: Config) -> axum::Router {
+    let max_request_size = config.max_request_size();
+    let content_security_policy = config.content_security_policy;
+    let semaphore = config
+        .max_concurrent_requests
+        .map(|max| Arc::new(Semaphore::new(max)));
+
+    let compression = CompressionLayer::new()
+        .quality(config.compression_level)
+        .compress_when(
+            SizeAbove::new(config.compression_min_size)
+                .and(NotForContentType::GRPC)
+                .and(NotForContentType::IMAGES),
+        );
+
+    Router::new()
+        .merge(web::routes())
+        .merge(rest::routes())
+        .layer(middleware::from_fn(move |req, next| {
+            decompress::decompress(req, next, max_request_size)
+        }))
+        .layer(middleware::from_fn(move |req, next| {
+            csp::csp(req, next, content_security_policy)
+        }))
+        .layer(middleware::from_fn(move |req, next| {
+            limit::concurrency_limit(req, next, semaphore.clone())
+        }))
+        .layer(Extension(cache_layer))
+        .layer(Extension(metrics::Metrics::default()))
+        .layer(Extension(limit::InsertLimiter::new(
+            config.max_inserts_per_minute,
+        )))
+        .layer(Extension(Arc::new(config)))
+        .layer(TimeoutLayer::new(Duration::from_secs(5)))
+        .layer(TraceLayer::new_for_http())
+        .layer(compression)
+        .layer(RequestBodyLimitLayer::new(max_request_size))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn content_is_redacted() {
+        let entry = Entry {
+            text: "secret".to_string(),
+            append_token: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+
+        let debug = format!("{entry:?}");
+        assert!(!debug.contains("secret"));
+        assert!(!debug.contains("hunter2"));
+        assert!(debug.contains("<6 bytes>"));
+
+        let config = Config::default();
+        let content = LogContent::new(&entry.text, &config).to_string();
+        assert_eq!(content, "<6 bytes redacted>");
+
+        let config = Config {
+            log_content: true,
+            ..Default::default()
+        };
+
+        let content = LogContent::new(&entry.text, &config).to_string();
+        assert_eq!(content, r#""secret""#);
+    }
+
+    #[test]
+    fn server_error_details_are_hidden() {
+        let (status, message) = Error::TooManyLines(3).into_response_parts();
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(message, "paste exceeds the maximum of 3 lines");
+
+        let (status, message) =
+            Error::Sqlite(rusqlite::Error::InvalidColumnName("secret".to_string()))
+                .into_response_parts();
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(message, "internal server error");
+    }
+
+    #[test]
+    fn sanitize_filenames() {
+        assert_eq!(
+            sanitize_filename("main.rs", 255).as_deref(),
+            Some("main.rs")
+        );
+        assert_eq!(
+            sanitize_filename("/tmp/a b.log", 255).as_deref(),
+            Some("a b.log")
+        );
+        assert_eq!(
+            sanitize_filename("C:\\dir\\x.txt", 255).as_deref(),
+            Some("x.txt")
+        );
+        assert_eq!(
+            sanitize_filename("ä\"\n.md", 255).as_deref(),
+            Some("___.md")
+        );
+        assert_eq!(sanitize_filename("abcdef", 3).as_deref(), Some("abc"));
+        assert_eq!(
+            sanitize_filename("abcdef.txt", 8).as_deref(),
+            Some("abcd.txt")
+        );
+        assert_eq!(sanitize_filename(".abcdef", 3).as_deref(), Some(".ab"));
+        assert_eq!(
+            sanitize_filename("a.verylongextension", 8).as_deref(),
+            Some("a.verylo")
+        );
+        assert_eq!(sanitize_filename("dir/", 255), None);
+        assert_eq!(sanitize_filename("..", 255), None);
+    }
+}
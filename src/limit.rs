@@ -0,0 +1,235 @@
+use crate::Error;
+use axum::body::Body;
+use axum::http::{Request, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use http_body::Limited;
+use std::collections::{HashMap, VecDeque};
+use std::net::IpAddr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+
+/// Sliding window within which inserts of a client are counted.
+const INSERT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Per-client insert times within the sliding window.
+struct Inserts {
+    times: HashMap<IpAddr, VecDeque<Instant>>,
+    last_sweep: Instant,
+}
+
+/// Limits the number of inserts per client address within a minute. Without a maximum every
+/// insert is allowed and nothing is recorded.
+#[derive(Clone)]
+pub struct InsertLimiter {
+    max: Option<usize>,
+    inserts: Arc<Mutex<Inserts>>,
+}
+
+impl InsertLimiter {
+    pub fn new(max: Option<usize>) -> Self {
+        Self {
+            max,
+            inserts: Arc::new(Mutex::new(Inserts {
+                times: HashMap::new(),
+                last_sweep: Instant::now(),
+            })),
+        }
+    }
+
+    /// Record an insert of `ip` or fail with [`Error::RateLimited`] if it already reached the
+    /// maximum within the last minute.
+    pub fn check(&self, ip: IpAddr) -> Result<(), Error> {
+        self.check_at(ip, Instant::now())
+    }
+
+    fn check_at(&self, ip: IpAddr, now: Instant) -> Result<(), Error> {
+        let max = match self.max {
+            Some(max) => max,
+            None => return Ok(()),
+        };
+
+        let mut inserts = self.inserts.lock().unwrap();
+
+        // Forget clients without recent inserts once per window so that the map does not grow
+        // with every address ever seen.
+        if now.duration_since(inserts.last_sweep) >= INSERT_WINDOW {
+            inserts.times.retain(|_, times| {
+                times
+                    .back()
+                    .is_some_and(|last| now.duration_since(*last) < INSERT_WINDOW)
+            });
+            inserts.last_sweep = now;
+        }
+
+        let times = inserts.times.entry(ip).or_default();
+
+        while times
+            .front()
+            .is_some_and(|first| now.duration_since(*first) >= INSERT_WINDOW)
+        {
+            times.pop_front();
+        }
+
+        if times.len() >= max {
+            tracing::warn!(%ip, "rate limiting inserts");
+            return Err(Error::RateLimited(max));
+        }
+
+        times.push_back(now);
+
+        Ok(())
+    }
+}
+
+/// Respond with 503 instead of handling the request if all permits of `semaphore` are taken, i.e.
+/// the maximum number of requests is already being handled. A permit is held until the response
+/// is ready, streamed response bodies do not count.
+pub async fn concurrency_limit(
+    req: Request<Limited<Body>>,
+    next: Next<Limited<Body>>,
+    semaphore: Option<Arc<Semaphore>>,
+) -> Response {
+    let _permit = match semaphore.map(Semaphore::try_acquire_owned) {
+        Some(Ok(permit)) => Some(permit),
+        Some(Err(_)) => {
+            tracing::warn!("shedding request, too many concurrent requests");
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        }
+        None => None,
+    };
+
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::Config;
+    use crate::test_helpers::{make_app_with_config, Client};
+    use http::StatusCode;
+    use tokio::io::AsyncWriteExt;
+    use tokio::net::TcpStream;
+
+    #[tokio::test]
+    async fn shed_and_recover() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            max_concurrent_requests: Some(1),
+            ..Default::default()
+        })?);
+
+        assert_eq!(client.get("/").send().await?.status(), StatusCode::OK);
+
+        // Occupy the only permit with a request whose body never completes.
+        let request = concat!(
+            "POST / HTTP/1.1\r\n",
+            "Host: localhost\r\n",
+            "Content-Type: application/x-www-form-urlencoded\r\n",
+            "Content-Length: 100\r\n",
+            "\r\n",
+            "text=foo",
+        );
+
+        let mut stream = TcpStream::connect(client.addr()).await?;
+        stream.write_all(request.as_bytes()).await?;
+
+        let mut shed = false;
+
+        for _ in 0..50 {
+            if client.get("/").send().await?.status() == StatusCode::SERVICE_UNAVAILABLE {
+                shed = true;
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(shed);
+
+        drop(stream);
+
+        let mut recovered = false;
+
+        for _ in 0..50 {
+            if client.get("/").send().await?.status() == StatusCode::OK {
+                recovered = true;
+                break;
+            }
+
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        assert!(recovered);
+
+        Ok(())
+    }
+
+    #[test]
+    fn sliding_insert_window() {
+        let limiter = InsertLimiter::new(Some(2));
+        let ip: IpAddr = "10.0.0.1".parse().unwrap();
+        let other: IpAddr = "10.0.0.2".parse().unwrap();
+        let start = Instant::now();
+
+        assert!(limiter.check_at(ip, start).is_ok());
+        assert!(limiter
+            .check_at(ip, start + Duration::from_secs(30))
+            .is_ok());
+        assert!(matches!(
+            limiter.check_at(ip, start + Duration::from_secs(40)),
+            Err(Error::RateLimited(2))
+        ));
+        assert!(limiter
+            .check_at(other, start + Duration::from_secs(40))
+            .is_ok());
+
+        // The first insert left the window, the second one is still in it.
+        assert!(limiter
+            .check_at(ip, start + Duration::from_secs(61))
+            .is_ok());
+        assert!(limiter
+            .check_at(ip, start + Duration::from_secs(62))
+            .is_err());
+
+        let unlimited = InsertLimiter::new(None);
+
+        for _ in 0..100 {
+            assert!(unlimited.check_at(ip, start).is_ok());
+        }
+    }
+
+    #[tokio::test]
+    async fn rate_limited_inserts() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            max_inserts_per_minute: Some(1),
+            ..Default::default()
+        })?);
+
+        let entry = crate::Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        let payload: serde_json::Value = res.json().await?;
+        assert!(payload["message"].as_str().unwrap().contains("too many"));
+
+        let res = client
+            .post("/")
+            .form(&[("text", "FooBarBaz"), ("expires", "0")])
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert!(res.text().await?.contains("too many"));
+
+        // Reads are not limited.
+        assert_eq!(client.get("/").send().await?.status(), StatusCode::OK);
+
+        Ok(())
+    }
+}
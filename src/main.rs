@@ -1,104 +1,59 @@
-use crate::db::Database;
-use axum::http::StatusCode;
-use axum::{Extension, Server};
-use serde::{Deserialize, Serialize};
+use axum::extract::connect_info::IntoMakeServiceWithConnectInfo;
+use axum::Server;
+use axum_server::tls_rustls::RustlsConfig;
 use std::env::{self, VarError};
 use std::io;
-use std::num::TryFromIntError;
+use std::net::SocketAddr;
 use std::path::PathBuf;
-use std::time::Duration;
-use tower_http::compression::CompressionLayer;
-use tower_http::limit::RequestBodyLimitLayer;
-use tower_http::timeout::TimeoutLayer;
-use tower_http::trace::TraceLayer;
-
-mod cache;
-mod db;
-mod highlight;
-mod id;
-mod rest;
-#[cfg(test)]
-mod test_helpers;
-mod web;
-
-#[derive(thiserror::Error, Debug)]
-pub enum Error {
-    #[error("axum http error: {0}")]
-    Axum(#[from] axum::http::Error),
-    #[error("deletion time expired")]
-    DeletionTimeExpired,
-    #[error("sqlite error: {0}")]
-    Sqlite(#[from] rusqlite::Error),
-    #[error("migrations error: {0}")]
-    Migration(#[from] rusqlite_migration::Error),
-    #[error("wrong size")]
-    WrongSize,
-    #[error("illegal characters")]
-    IllegalCharacters,
-    #[error("integer conversion error: {0}")]
-    IntConversion(#[from] TryFromIntError),
-    #[error("join error: {0}")]
-    Join(#[from] tokio::task::JoinError),
-    #[error("syntax highlighting error: {0}")]
-    SyntaxHighlighting(#[from] syntect::Error),
-    #[error("syntax parsing error: {0}")]
-    SyntaxParsing(#[from] syntect::parsing::ParsingError),
-    #[error("time formatting error: {0}")]
-    TimeFormatting(#[from] time::error::Format),
+use wastebin::config::Config;
+use wastebin::db::{self, Database};
+use wastebin::{cache, make_app};
+
+async fn shutdown_signal() {
+    tokio::signal::ctrl_c()
+        .await
+        .expect("failed to listen to ctrl-c");
 }
 
-#[derive(Default, Debug, Serialize, Deserialize)]
-pub struct Entry {
-    /// Content
-    pub text: String,
-    /// File extension
-    pub extension: Option<String>,
-    /// Expiration in seconds from now
-    pub expires: Option<u32>,
-    /// Delete if read
-    pub burn_after_reading: Option<bool>,
-    /// Seconds since creation
-    pub seconds_since_creation: u32,
-}
-
-pub type Router = axum::Router<http_body::Limited<axum::body::Body>>;
-
-impl From<Error> for StatusCode {
-    fn from(err: Error) -> Self {
-        match err {
-            Error::Sqlite(err) => match err {
-                rusqlite::Error::QueryReturnedNoRows => StatusCode::NOT_FOUND,
-                _ => StatusCode::INTERNAL_SERVER_ERROR,
-            },
-            Error::IllegalCharacters | Error::WrongSize | Error::DeletionTimeExpired => {
-                StatusCode::BAD_REQUEST
-            }
-            Error::Join(_)
-            | Error::IntConversion(_)
-            | Error::TimeFormatting(_)
-            | Error::Migration(_)
-            | Error::SyntaxHighlighting(_)
-            | Error::SyntaxParsing(_)
-            | Error::Axum(_) => StatusCode::INTERNAL_SERVER_ERROR,
+/// Serve HTTP/1.1 and HTTP/2 with prior knowledge in plain text or, if `tls` is given, negotiate
+/// HTTP/2 or HTTP/1.1 via ALPN.
+async fn serve(
+    addr: SocketAddr,
+    service: IntoMakeServiceWithConnectInfo<axum::Router, SocketAddr>,
+    tls: Option<RustlsConfig>,
+) -> Result<(), Box<dyn std::error::Error>> {
+    match tls {
+        None => {
+            Server::bind(&addr)
+                .serve(service)
+                .with_graceful_shutdown(shutdown_signal())
+                .await?;
+        }
+        Some(tls) => {
+            let handle = axum_server::Handle::new();
+            let shutdown = handle.clone();
+
+            tokio::spawn(async move {
+                shutdown_signal().await;
+                shutdown.graceful_shutdown(None);
+            });
+
+            axum_server::tls_rustls::bind_rustls(addr, tls)
+                .handle(handle)
+                .serve(service)
+                .await?;
         }
     }
-}
 
-pub(crate) fn make_app(cache_layer: cache::Layer, max_body_size: usize) -> axum::Router {
-    Router::new()
-        .merge(web::routes())
-        .merge(rest::routes())
-        .layer(Extension(cache_layer))
-        .layer(TimeoutLayer::new(Duration::from_secs(5)))
-        .layer(TraceLayer::new_for_http())
-        .layer(CompressionLayer::new())
-        .layer(RequestBodyLimitLayer::new(max_body_size))
+    Ok(())
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Box<dyn std::error::Error>> {
     tracing_subscriber::fmt::init();
 
+    let config = Config::from_env()?;
+
     let database = match env::var("WASTEBIN_DATABASE_PATH") {
         Ok(path) => Ok(Database::new(db::Open::Path(PathBuf::from(path)))?),
         Err(VarError::NotUnicode(_)) => Err(io::Error::new(
@@ -106,7 +61,8 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             "WASTEBIN_DATABASE_PATH contains non-unicode data",
         )),
         Err(VarError::NotPresent) => Ok(Database::new(db::Open::Memory)?),
-    }?;
+    }?
+    .with_compression(config.storage_compression());
 
     let cache_size =
         env::var("WASTEBIN_CACHE_SIZE").map_or_else(|_| Ok(128), |s| s.parse::<usize>())?;
@@ -116,27 +72,64 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     let addr_port =
         env::var("WASTEBIN_ADDRESS_PORT").unwrap_or_else(|_| "0.0.0.0:8088".to_string());
 
-    let max_body_size = env::var("WASTEBIN_MAX_BODY_SIZE")
-        .map_or_else(|_| Ok(1024 * 1024), |s| s.parse::<usize>())?;
-
     tracing::debug!("serving on {addr_port}");
     tracing::debug!("caching {cache_size} paste highlights");
-    tracing::debug!("restricting maximum body size to {max_body_size} bytes");
+    tracing::debug!(
+        "restricting maximum paste size to {} bytes",
+        config.max_body_size
+    );
+
+    let tls = match &config.tls {
+        Some(tls) => {
+            tracing::debug!("serving TLS with certificate {}", tls.cert.display());
+            Some(RustlsConfig::from_pem_file(&tls.cert, &tls.key).await?)
+        }
+        None => None,
+    };
+
+    let addr: SocketAddr = addr_port.parse()?;
+    let http3 = config.tls.clone().filter(|tls| tls.http3);
 
-    let service = make_app(cache_layer.clone(), max_body_size).into_make_service();
+    #[cfg(not(feature = "http3"))]
+    if http3.is_some() {
+        return Err("WASTEBIN_HTTP3 requires building with the http3 feature".into());
+    }
 
-    let server = Server::bind(&addr_port.parse()?)
-        .serve(service)
-        .with_graceful_shutdown(async {
-            tokio::signal::ctrl_c()
-                .await
-                .expect("failed to listen to ctrl-c");
-        });
+    #[cfg(feature = "http3")]
+    let max_request_size = config.max_request_size();
+
+    let app = make_app(cache_layer.clone(), config);
+
+    #[cfg(feature = "http3")]
+    let app = match &http3 {
+        Some(_) => app.layer(wastebin::http3::alt_svc(addr.port())),
+        None => app,
+    };
+
+    let server = serve(
+        addr,
+        app.clone()
+            .into_make_service_with_connect_info::<SocketAddr>(),
+        tls,
+    );
+
+    let http3_server = async move {
+        #[cfg(feature = "http3")]
+        if let Some(tls) = http3 {
+            tracing::debug!("serving HTTP/3 on udp {addr}");
+            return Ok(wastebin::http3::serve(addr, &tls, app, max_request_size).await?);
+        }
+
+        std::future::pending::<Result<(), Box<dyn std::error::Error>>>().await
+    };
 
     tokio::select! {
         res = server => {
             res?
         },
+        res = http3_server => {
+            res?
+        },
         res = cache::purge_periodically(cache_layer) => {
             res?
         }
@@ -0,0 +1,160 @@
+use serde::Serialize;
+use std::fmt::Write;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Number of one-minute buckets, covering a full day.
+const BUCKETS: usize = 24 * 60;
+
+/// Ring buffer of per-minute insert counts.
+struct Buckets {
+    counts: Vec<u64>,
+    /// Minute each bucket was last written in.
+    minutes: Vec<u64>,
+    total: u64,
+}
+
+/// Insert counts within trailing time windows.
+#[derive(Debug, Serialize)]
+pub struct Stats {
+    pub total: u64,
+    pub last_minute: u64,
+    pub last_hour: u64,
+    pub last_day: u64,
+}
+
+/// In-memory insert rate metrics.
+#[derive(Clone)]
+pub struct Metrics {
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+fn current_minute() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |duration| duration.as_secs() / 60)
+}
+
+impl Buckets {
+    fn new() -> Self {
+        Self {
+            counts: vec![0; BUCKETS],
+            minutes: vec![0; BUCKETS],
+            total: 0,
+        }
+    }
+
+    fn record(&mut self, minute: u64) {
+        let index = (minute % BUCKETS as u64) as usize;
+
+        if self.minutes[index] != minute {
+            self.minutes[index] = minute;
+            self.counts[index] = 0;
+        }
+
+        self.counts[index] += 1;
+        self.total += 1;
+    }
+
+    /// Sum of inserts within the `window` minutes up to and including `minute`.
+    fn count(&self, minute: u64, window: u64) -> u64 {
+        self.minutes
+            .iter()
+            .zip(&self.counts)
+            .filter(|(m, _)| **m <= minute && minute - **m < window)
+            .map(|(_, count)| count)
+            .sum()
+    }
+
+    fn stats(&self, minute: u64) -> Stats {
+        Stats {
+            total: self.total,
+            last_minute: self.count(minute, 1),
+            last_hour: self.count(minute, 60),
+            last_day: self.count(minute, BUCKETS as u64),
+        }
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self {
+            buckets: Arc::new(Mutex::new(Buckets::new())),
+        }
+    }
+}
+
+impl Metrics {
+    /// Count a newly inserted paste.
+    pub fn record_insert(&self) {
+        self.buckets.lock().unwrap().record(current_minute());
+    }
+
+    pub fn stats(&self) -> Stats {
+        self.buckets.lock().unwrap().stats(current_minute())
+    }
+}
+
+impl Stats {
+    /// Render stats in the Prometheus text exposition format.
+    pub fn to_prometheus(&self) -> String {
+        let mut out = String::new();
+
+        out.push_str("# HELP wastebin_inserts_total Number of pastes created since start.\n");
+        out.push_str("# TYPE wastebin_inserts_total counter\n");
+        let _ = writeln!(out, "wastebin_inserts_total {}", self.total);
+
+        out.push_str("# HELP wastebin_inserts_recent Number of pastes created within a window.\n");
+        out.push_str("# TYPE wastebin_inserts_recent gauge\n");
+
+        for (window, count) in [
+            ("1m", self.last_minute),
+            ("1h", self.last_hour),
+            ("1d", self.last_day),
+        ] {
+            let _ = writeln!(
+                out,
+                "wastebin_inserts_recent{{window=\"{window}\"}} {count}"
+            );
+        }
+
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn inserts_bump_current_bucket() {
+        let mut buckets = Buckets::new();
+        let minute = 1_000_000;
+
+        buckets.record(minute - 120);
+        buckets.record(minute - 2);
+        buckets.record(minute);
+        buckets.record(minute);
+
+        let stats = buckets.stats(minute);
+        assert_eq!(stats.total, 4);
+        assert_eq!(stats.last_minute, 2);
+        assert_eq!(stats.last_hour, 3);
+        assert_eq!(stats.last_day, 4);
+
+        let stats = buckets.stats(minute + BUCKETS as u64);
+        assert_eq!(stats.last_day, 0);
+    }
+
+    #[test]
+    fn wrapped_bucket_is_reset() {
+        let mut buckets = Buckets::new();
+
+        buckets.record(5);
+        buckets.record(5 + BUCKETS as u64);
+
+        let stats = buckets.stats(5 + BUCKETS as u64);
+        assert_eq!(stats.last_minute, 1);
+        assert_eq!(stats.total, 2);
+    }
+}
@@ -0,0 +1,4 @@
+CREATE TABLE tombstones(
+    id INTEGER PRIMARY KEY,
+    deleted_at TEXT NOT NULL
+);
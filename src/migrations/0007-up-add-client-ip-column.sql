@@ -0,0 +1 @@
+ALTER TABLE entries ADD COLUMN client_ip TEXT;
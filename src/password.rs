@@ -0,0 +1,71 @@
+use crate::crypto;
+use crate::{Entry, Error};
+
+/// Replace the text of a new `entry` by the text encrypted with its password. Entries without
+/// password are left as they are.
+pub async fn protect(entry: &mut Entry) -> Result<(), Error> {
+    if let Some(password) = entry.password.take() {
+        let text = std::mem::take(&mut entry.text);
+        entry.ciphertext = Some(crypto::encrypt(password, text).await?);
+    }
+
+    Ok(())
+}
+
+/// Decrypt the text of a stored `entry` with `password`. Entries without ciphertext need no
+/// password, otherwise a missing or wrong password fails with [`Error::Unauthorized`] because the
+/// ciphertext does not authenticate. The ciphertext is kept to mark the entry as protected.
+pub async fn unlock(entry: &mut Entry, password: Option<String>) -> Result<(), Error> {
+    match (&entry.ciphertext, password) {
+        (None, _) => Ok(()),
+        (Some(_), None) => Err(Error::Unauthorized),
+        (Some(data), Some(password)) => {
+            entry.text = crypto::decrypt(password, data.clone()).await?;
+            Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn protect_and_unlock() -> Result<(), Error> {
+        let mut entry = Entry {
+            text: "FooBarBaz".to_string(),
+            password: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+
+        protect(&mut entry).await?;
+        assert!(entry.text.is_empty());
+        assert!(entry.password.is_none());
+        assert!(entry.ciphertext.is_some());
+
+        assert!(matches!(
+            unlock(&mut entry.clone(), Some("hunter3".to_string())).await,
+            Err(Error::Unauthorized)
+        ));
+
+        assert!(matches!(
+            unlock(&mut entry.clone(), None).await,
+            Err(Error::Unauthorized)
+        ));
+
+        unlock(&mut entry, Some("hunter2".to_string())).await?;
+        assert_eq!(entry.text, "FooBarBaz");
+        assert!(entry.ciphertext.is_some());
+
+        let mut plain = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        protect(&mut plain).await?;
+        assert_eq!(plain.text, "FooBarBaz");
+        assert!(plain.ciphertext.is_none());
+
+        Ok(())
+    }
+}
@@ -1,32 +1,155 @@
+use crate::base::BaseUrl;
 use crate::cache::Layer;
+use crate::conditional::{self, Caching};
+use crate::config::{Config, SlugCollision};
 use crate::id::Id;
-use crate::{Entry, Error, Router};
+use crate::ip::ClientIp;
+use crate::limit::InsertLimiter;
+use crate::metrics::{Metrics, Stats};
+use crate::password;
+use crate::{deletion_time_remaining, validate_text, Entry, Error, LogContent, Router};
 use axum::extract::Path;
-use axum::http::StatusCode;
+use axum::headers::authorization::Bearer;
+use axum::headers::{Authorization, HeaderMapExt, IfNoneMatch};
+use axum::http::header::{self, HeaderName};
+use axum::http::{HeaderMap, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
 use axum::routing::{get, post};
-use axum::{Extension, Json};
+use axum::{Extension, Json, TypedHeader};
 use rand::Rng;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use subtle::ConstantTimeEq;
+use tower_http::set_header::SetResponseHeaderLayer;
+
+/// Maximum number of pastes returned by a single fetch request.
+const MAX_FETCH_IDS: usize = 100;
+
+/// Current version of the API, served below `/api/v1` and sent as `X-API-Version` header.
+const API_VERSION: &str = "1";
+
+#[derive(Serialize, Deserialize)]
+pub struct ErrorPayload {
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RedirectResponse {
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub append_token: Option<String>,
+    /// Set if the paste is deleted on its first read.
+    #[serde(default)]
+    pub burn: bool,
+}
 
 #[derive(Serialize)]
-struct ErrorPayload {
-    message: String,
+struct ThemesResponse {
+    themes: Vec<String>,
+}
+
+#[derive(Deserialize)]
+struct FetchRequest {
+    ids: Vec<String>,
 }
 
 #[derive(Serialize)]
-struct RedirectResponse {
-    path: String,
+struct FetchedEntry {
+    text: String,
+    extension: Option<String>,
+}
+
+#[derive(Deserialize)]
+struct AppendRequest {
+    text: String,
+}
+
+#[derive(Serialize)]
+struct AppendResponse {
+    size: usize,
+}
+
+#[derive(Serialize)]
+struct ValidResponse {
+    ok: bool,
+}
+
+#[derive(Serialize)]
+struct MetadataResponse {
+    /// Missing for burn after reading pastes, which metadata requests must not consume.
+    text: Option<String>,
+    extension: Option<String>,
+    /// Total lifetime in seconds.
+    expires: Option<u32>,
+    burn_after_reading: bool,
+    seconds_since_creation: u32,
+    seconds_until_expiry: Option<u32>,
+}
+
+impl From<Entry> for MetadataResponse {
+    fn from(entry: Entry) -> Self {
+        let burn_after_reading = entry.burn_after_reading.unwrap_or(false);
+
+        Self {
+            text: if burn_after_reading {
+                None
+            } else {
+                Some(entry.text)
+            },
+            extension: entry.extension,
+            expires: entry
+                .expires
+                .map(|remaining| remaining.saturating_add(entry.seconds_since_creation)),
+            burn_after_reading,
+            seconds_since_creation: entry.seconds_since_creation,
+            seconds_until_expiry: entry.expires,
+        }
+    }
+}
+
+#[derive(Serialize)]
+struct InfoResponse {
+    deletion_possible: bool,
+    deletion_seconds_remaining: Option<u64>,
+}
+
+impl InfoResponse {
+    /// Report whether `delete` would succeed given the `window`, which is `None` if a valid
+    /// delete secret lifts it.
+    fn new(entry: &Entry, window: Option<u64>) -> Self {
+        match window {
+            Some(window) => {
+                let deletion_seconds_remaining =
+                    deletion_time_remaining(entry.seconds_since_creation, window);
+
+                Self {
+                    deletion_possible: deletion_seconds_remaining.is_some(),
+                    deletion_seconds_remaining,
+                }
+            }
+            None => Self {
+                deletion_possible: true,
+                deletion_seconds_remaining: None,
+            },
+        }
+    }
+
+    /// Deletion is refused outright, e.g. because of a wrong delete secret.
+    fn denied() -> Self {
+        Self {
+            deletion_possible: false,
+            deletion_seconds_remaining: None,
+        }
+    }
 }
 
 type ErrorResponse = (StatusCode, Json<ErrorPayload>);
 
 impl From<Error> for ErrorResponse {
     fn from(err: Error) -> Self {
-        let payload = Json::from(ErrorPayload {
-            message: err.to_string(),
-        });
-
-        (err.into(), payload)
+        let (status, message) = err.into_response_parts();
+        (status, Json::from(ErrorPayload { message }))
     }
 }
 
@@ -35,43 +158,1304 @@ async fn health() -> StatusCode {
     StatusCode::OK
 }
 
+async fn random_token() -> Result<String, Error> {
+    let n = tokio::task::spawn_blocking(|| {
+        let mut rng = rand::thread_rng();
+        rng.gen::<u128>()
+    })
+    .await?;
+
+    Ok(format!("{n:032x}"))
+}
+
 async fn insert(
-    Json(entry): Json<Entry>,
+    Json(mut entry): Json<Entry>,
     layer: Extension<Layer>,
+    Extension(config): Extension<Arc<Config>>,
+    metrics: Extension<Metrics>,
+    limiter: Extension<InsertLimiter>,
+    ClientIp(ip): ClientIp,
+    BaseUrl(base): BaseUrl,
 ) -> Result<Json<RedirectResponse>, ErrorResponse> {
-    let id: Id = tokio::task::spawn_blocking(|| {
-        let mut rng = rand::thread_rng();
-        rng.gen::<u32>()
-    })
-    .await
-    .map_err(Error::from)?
-    .into();
+    entry.normalize(&config);
+    entry.validate(&config)?;
+
+    tracing::debug!(%ip, content = %LogContent::new(&entry.text, &config), "inserting paste");
+
+    limiter.check(ip)?;
+    let quota = Layer::claim_quota(&mut entry, ip, &config);
+
+    let slug = entry.slug.take().filter(|_| config.vanity_slugs);
+
+    if entry.appendable.unwrap_or(false) {
+        entry.append_token = Some(random_token().await?);
+    }
+
+    password::protect(&mut entry).await?;
+
+    let id = match slug {
+        Some(ref slug) => {
+            let id = Id::from_slug(slug)?;
+
+            match layer.insert(id, entry.clone(), quota).await {
+                Err(Error::IdTaken) if config.slug_collision == SlugCollision::Random => {
+                    layer.insert_random(entry.clone(), quota).await?
+                }
+                result => result.map(|_| id)?,
+            }
+        }
+        None => layer.insert_random(entry.clone(), quota).await?,
+    };
+
+    metrics.record_insert();
+
+    let path = id.page(&base, entry.extension.as_deref());
+
+    Ok(Json::from(RedirectResponse {
+        path,
+        append_token: entry.append_token,
+        burn: entry.burn_after_reading.unwrap_or(false),
+    }))
+}
+
+/// Run all insert-time checks on `entry` without storing it.
+#[allow(clippy::unused_async)]
+async fn validate(
+    Json(mut entry): Json<Entry>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Json<ValidResponse>, ErrorResponse> {
+    entry.normalize(&config);
+    entry.validate(&config)?;
+
+    if let Some(slug) = entry.slug.as_deref().filter(|_| config.vanity_slugs) {
+        Id::from_slug(slug)?;
+    }
+
+    Ok(Json::from(ValidResponse { ok: true }))
+}
+
+/// Password of a protected paste sent in the `X-Paste-Password` header.
+fn paste_password(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get("x-paste-password")
+        .and_then(|value| value.to_str().ok())
+        .map(ToString::to_string)
+}
+
+fn accepts_json(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("application/json"))
+}
+
+/// Serve the paste as JSON metadata if requested with `Accept: application/json`, otherwise as
+/// raw text.
+async fn entry(
+    id: Path<String>,
+    headers: HeaderMap,
+    layer: Extension<Layer>,
+    base: BaseUrl,
+) -> Result<Response, ErrorResponse> {
+    if accepts_json(&headers) {
+        Ok(metadata(id, headers, layer).await?.into_response())
+    } else {
+        Ok(raw(id, headers, layer, base).await?.into_response())
+    }
+}
+
+/// Metadata and, unless it is burn after reading, the text of a paste. The paste is not consumed.
+async fn metadata(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    layer: Extension<Layer>,
+) -> Result<Json<MetadataResponse>, ErrorResponse> {
+    let mut entry = layer.peek(Id::try_from(id.as_str())?).await?;
+    password::unlock(&mut entry, paste_password(&headers)).await?;
+    Ok(Json::from(MetadataResponse::from(entry)))
+}
+
+/// Raw text of a paste. Cacheable pastes are tagged and answered with 304 if the client already
+/// has them.
+async fn raw(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    layer: Extension<Layer>,
+    BaseUrl(base): BaseUrl,
+) -> Result<Response, ErrorResponse> {
+    let id = Id::try_from(id.as_str())?;
+    let entry = layer.get(id, paste_password(&headers)).await?;
+    let caching = Caching::paste(
+        conditional::cacheable(&entry),
+        &(id.as_u32(), &entry.text),
+        entry.seconds_since_creation,
+        entry.appendable.unwrap_or(false),
+    );
+    let links = id.links(&base, "txt");
+
+    if caching.not_modified(&headers.typed_get::<IfNoneMatch>().map(TypedHeader)) {
+        return Ok((StatusCode::NOT_MODIFIED, links, caching.headers()).into_response());
+    }
+
+    Ok((links, caching.headers(), entry.text).into_response())
+}
+
+/// Return the pastes for all requested ids that exist. Unknown ids, burn after reading and password
+/// protected pastes are omitted, so burn after reading pastes are never consumed.
+async fn fetch(
+    Json(request): Json<FetchRequest>,
+    layer: Extension<Layer>,
+) -> Result<Json<HashMap<String, FetchedEntry>>, ErrorResponse> {
+    if request.ids.len() > MAX_FETCH_IDS {
+        Err(Error::TooManyIds(MAX_FETCH_IDS))?;
+    }
+
+    let mut entries = HashMap::new();
+
+    for id in request.ids {
+        let entry = match Id::try_from(id.as_str()) {
+            Ok(parsed) => layer.peek(parsed).await,
+            Err(err) => Err(err),
+        };
+
+        match entry {
+            Ok(entry)
+                if !entry.burn_after_reading.unwrap_or(false) && entry.ciphertext.is_none() =>
+            {
+                entries.insert(
+                    id,
+                    FetchedEntry {
+                        text: entry.text,
+                        extension: entry.extension,
+                    },
+                );
+            }
+            Ok(_)
+            | Err(
+                Error::Sqlite(rusqlite::Error::QueryReturnedNoRows)
+                | Error::WrongSize
+                | Error::IllegalCharacters,
+            ) => {}
+            Err(err) => Err(err)?,
+        }
+    }
+
+    Ok(Json::from(entries))
+}
 
-    let path = id.to_url_path(&entry);
+async fn info(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    layer: Extension<Layer>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Json<InfoResponse>, ErrorResponse> {
+    let mut entry = layer.peek(Id::try_from(id.as_str())?).await?;
+    password::unlock(&mut entry, paste_password(&headers)).await?;
+
+    let info = match delete_secret_matches(&config, &headers) {
+        Ok(ignore_window) => {
+            InfoResponse::new(&entry, (!ignore_window).then_some(config.deletion_window))
+        }
+        Err(_) => InfoResponse::denied(),
+    };
 
-    layer.insert(id, entry).await?;
-    Ok(Json::from(RedirectResponse { path }))
+    Ok(Json::from(info))
 }
 
-async fn raw(Path(id): Path<String>, layer: Extension<Layer>) -> Result<String, ErrorResponse> {
-    Ok(layer.get(Id::try_from(id.as_str())?).await?.text)
+/// Check the `X-Delete-Secret` header, returning `true` if it allows ignoring the deletion
+/// window. The header is ignored if no secret is configured.
+fn delete_secret_matches(config: &Config, headers: &HeaderMap) -> Result<bool, Error> {
+    match (&config.delete_secret, headers.get("x-delete-secret")) {
+        (Some(secret), Some(value)) if bool::from(value.as_bytes().ct_eq(secret.as_bytes())) => {
+            Ok(true)
+        }
+        (Some(_), Some(_)) => Err(Error::Unauthorized),
+        _ => Ok(false),
+    }
 }
 
-async fn delete(Path(id): Path<String>, layer: Extension<Layer>) -> Result<(), ErrorResponse> {
+async fn delete(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    layer: Extension<Layer>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<StatusCode, ErrorResponse> {
+    let id = Id::try_from(id.as_str())?;
+    let ignore_window = delete_secret_matches(&config, &headers)?;
+    layer
+        .delete(
+            id,
+            config.delete_missing_ok,
+            (!ignore_window).then_some(config.deletion_window),
+        )
+        .await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// Append text to a paste created with `appendable` set, authorized by the `X-Append-Token`
+/// header. Size and line limits apply to the combined text.
+async fn append(
+    Path(id): Path<String>,
+    headers: HeaderMap,
+    Json(mut payload): Json<AppendRequest>,
+    layer: Extension<Layer>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Json<AppendResponse>, ErrorResponse> {
     let id = Id::try_from(id.as_str())?;
-    let entry = layer.get(id).await?;
 
-    if entry.seconds_since_creation > 60 {
-        Err(Error::DeletionTimeExpired)?
+    let token = headers
+        .get("x-append-token")
+        .and_then(|value| value.to_str().ok())
+        .ok_or(Error::Unauthorized)?
+        .to_string();
+
+    config.control_characters.apply(&mut payload.text);
+
+    let size = layer
+        .append(id, token, payload.text, move |text| {
+            validate_text(text, &config)
+        })
+        .await?;
+
+    Ok(Json::from(AppendResponse { size }))
+}
+
+#[allow(clippy::unused_async)]
+async fn themes(Extension(config): Extension<Arc<Config>>) -> Json<ThemesResponse> {
+    Json::from(ThemesResponse {
+        themes: config.themes.keys().cloned().collect(),
+    })
+}
+
+/// Check the bearer token for admin endpoints, which do not exist without a configured token.
+fn authorize(
+    config: &Config,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<(), Error> {
+    let expected = config.admin_token.as_deref().ok_or(Error::NotFound)?;
+
+    match authorization {
+        Some(TypedHeader(Authorization(bearer)))
+            if bool::from(bearer.token().as_bytes().ct_eq(expected.as_bytes())) =>
+        {
+            Ok(())
+        }
+        _ => Err(Error::Unauthorized),
     }
+}
+
+#[allow(clippy::unused_async)]
+async fn stats(
+    Extension(config): Extension<Arc<Config>>,
+    metrics: Extension<Metrics>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<Json<Stats>, ErrorResponse> {
+    authorize(&config, authorization)?;
+    Ok(Json::from(metrics.stats()))
+}
+
+#[allow(clippy::unused_async)]
+async fn prometheus(
+    Extension(config): Extension<Arc<Config>>,
+    metrics: Extension<Metrics>,
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+) -> Result<String, ErrorResponse> {
+    authorize(&config, authorization)?;
+    Ok(metrics.stats().to_prometheus())
+}
 
-    layer.delete(id).await?;
-    Ok(())
+/// API endpoints mounted below `prefix`, announcing the API version in their responses.
+fn api_routes(prefix: &str) -> Router {
+    Router::new()
+        .route(&format!("{prefix}/health"), get(health))
+        .route(&format!("{prefix}/entries"), post(insert))
+        .route(&format!("{prefix}/entries/validate"), post(validate))
+        .route(&format!("{prefix}/entries/fetch"), post(fetch))
+        .route(&format!("{prefix}/entries/:id"), get(entry).delete(delete))
+        .route(&format!("{prefix}/entries/:id/info"), get(info))
+        .route(&format!("{prefix}/entries/:id/append"), post(append))
+        .route(&format!("{prefix}/themes"), get(themes))
+        .route(&format!("{prefix}/admin/stats"), get(stats))
+        .layer(SetResponseHeaderLayer::overriding(
+            HeaderName::from_static("x-api-version"),
+            HeaderValue::from_static(API_VERSION),
+        ))
 }
 
+/// Versioned API routes with the unversioned `/api` paths kept as aliases of the current version.
 pub fn routes() -> Router {
     Router::new()
-        .route("/api/health", get(health))
-        .route("/api/entries", post(insert))
-        .route("/api/entries/:id", get(raw).delete(delete))
+        .merge(api_routes(&format!("/api/v{API_VERSION}")))
+        .merge(api_routes("/api"))
+        .route("/metrics", get(prometheus))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{ControlCharacters, TrailingNewline};
+    use crate::test_helpers::{make_app, make_app_with_config, Client};
+    use http::StatusCode;
+
+    #[tokio::test]
+    async fn size_limit_counts_bytes() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "é".repeat(2048),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let entry = Entry {
+            text: "é".repeat(2049),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+        let payload: ErrorPayload = res.json().await?;
+        assert_eq!(
+            payload.message,
+            "paste exceeds the maximum size of 4096 bytes"
+        );
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn oversized_request_rejected_early() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        // Far above the request limit derived from the maximum paste size of 4096 bytes.
+        let entry = Entry {
+            text: "a".repeat(64 * 1024),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let res = client
+            .post("/")
+            .form(&[("text", entry.text.as_str()), ("expires", "0")])
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn info_reports_deletion() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let id = path["path"].as_str().unwrap().trim_start_matches('/');
+
+        let res = client
+            .get(&format!("/api/entries/{id}/info"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let info: serde_json::Value = res.json().await?;
+        assert_eq!(info["deletion_possible"], true);
+        assert!(info["deletion_seconds_remaining"].as_u64().unwrap() > 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn info_reports_deletion_with_secret() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            delete_secret: Some("secret".to_string()),
+            deletion_window: 0,
+            ..Default::default()
+        })?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let url = format!("/api/entries{}/info", path["path"].as_str().unwrap());
+
+        let info: serde_json::Value = client.get(&url).send().await?.json().await?;
+        assert_eq!(info["deletion_possible"], false);
+
+        let info: serde_json::Value = client
+            .get(&url)
+            .header("x-delete-secret", "secret")
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(info["deletion_possible"], true);
+        assert_eq!(info["deletion_seconds_remaining"], serde_json::Value::Null);
+
+        let info: serde_json::Value = client
+            .get(&url)
+            .header("x-delete-secret", "wrong")
+            .send()
+            .await?
+            .json()
+            .await?;
+        assert_eq!(info["deletion_possible"], false);
+
+        Ok(())
+    }
+
+    #[test]
+    fn deletion_flag_flips_after_window() {
+        let mut entry = Entry {
+            seconds_since_creation: 59,
+            ..Default::default()
+        };
+
+        let info = InfoResponse::new(&entry, Some(60));
+        assert!(info.deletion_possible);
+        assert_eq!(info.deletion_seconds_remaining, Some(1));
+
+        entry.seconds_since_creation = 60;
+        let info = InfoResponse::new(&entry, Some(60));
+        assert!(!info.deletion_possible);
+        assert_eq!(info.deletion_seconds_remaining, None);
+
+        let info = InfoResponse::new(&entry, Some(0));
+        assert!(!info.deletion_possible);
+
+        let info = InfoResponse::new(&entry, None);
+        assert!(info.deletion_possible);
+        assert_eq!(info.deletion_seconds_remaining, None);
+
+        let info = InfoResponse::new(&entry, Some(3600));
+        assert_eq!(info.deletion_seconds_remaining, Some(3540));
+    }
+
+    #[tokio::test]
+    async fn colliding_slug() -> Result<(), Box<dyn std::error::Error>> {
+        for (collision, status) in [
+            (SlugCollision::Reject, StatusCode::CONFLICT),
+            (SlugCollision::Random, StatusCode::OK),
+        ] {
+            let client = Client::new(make_app_with_config(Config {
+                vanity_slugs: true,
+                slug_collision: collision,
+                ..Default::default()
+            })?);
+
+            let entry = Entry {
+                text: "FooBarBaz".to_string(),
+                slug: Some("wastea".to_string()),
+                ..Default::default()
+            };
+
+            let res = client.post("/api/entries").json(&entry).send().await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            let path: serde_json::Value = res.json().await?;
+            assert_eq!(path["path"], "/wastea");
+
+            let res = client.post("/api/entries").json(&entry).send().await?;
+            assert_eq!(res.status(), status);
+
+            if collision == SlugCollision::Random {
+                let path: serde_json::Value = res.json().await?;
+                let path = path["path"].as_str().unwrap();
+                assert_ne!(path, "/wastea");
+
+                let res = client.get(&format!("/api/entries{path}")).send().await?;
+                assert_eq!(res.text().await?, "FooBarBaz");
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn trailing_newline_is_added() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            trailing_newline: TrailingNewline::Add,
+            ..Default::default()
+        })?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let path = path["path"].as_str().unwrap();
+
+        let res = client.get(&format!("/api/entries{path}")).send().await?;
+        assert_eq!(res.text().await?, "FooBarBaz\n");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn control_characters() -> Result<(), Box<dyn std::error::Error>> {
+        let entry = Entry {
+            text: "Foo\0Bar\x1b[0m\tBaz\n".to_string(),
+            ..Default::default()
+        };
+
+        let client = Client::new(make_app_with_config(Config {
+            control_characters: ControlCharacters::Reject,
+            ..Default::default()
+        })?);
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        let client = Client::new(make_app_with_config(Config {
+            control_characters: ControlCharacters::Strip,
+            ..Default::default()
+        })?);
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let path: serde_json::Value = res.json().await?;
+        let path = path["path"].as_str().unwrap();
+
+        let res = client.get(&format!("/api/entries{path}")).send().await?;
+        assert_eq!(res.text().await?, "FooBar[0m\tBaz\n");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn extension_is_validated() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        for (extension, status) in [
+            ("rs", StatusCode::OK),
+            ("c++", StatusCode::OK),
+            ("r s", StatusCode::BAD_REQUEST),
+            ("<script>", StatusCode::BAD_REQUEST),
+            ("abcdefghijklmnopq", StatusCode::BAD_REQUEST),
+        ] {
+            let entry = Entry {
+                text: "FooBarBaz".to_string(),
+                extension: Some(extension.to_string()),
+                ..Default::default()
+            };
+
+            let res = client.post("/api/entries").json(&entry).send().await?;
+            assert_eq!(res.status(), status, "{extension}");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn fetch_multiple() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+        let mut ids = Vec::new();
+
+        for (text, extension, burn_after_reading) in [
+            ("Foo", Some("rs"), None),
+            ("Bar", None, None),
+            ("Baz", None, Some(true)),
+        ] {
+            let entry = Entry {
+                text: text.to_string(),
+                extension: extension.map(str::to_string),
+                burn_after_reading,
+                ..Default::default()
+            };
+
+            let res = client.post("/api/entries").json(&entry).send().await?;
+            let path: serde_json::Value = res.json().await?;
+            let path = path["path"].as_str().unwrap().trim_start_matches('/');
+            ids.push(path.split('.').next().unwrap().to_string());
+        }
+
+        ids.push("aaaaaa".to_string());
+        ids.push("invalid".to_string());
+
+        let res = client
+            .post("/api/entries/fetch")
+            .json(&serde_json::json!({ "ids": ids }))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let entries: serde_json::Value = res.json().await?;
+        let entries = entries.as_object().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[&ids[0]]["text"], "Foo");
+        assert_eq!(entries[&ids[0]]["extension"], "rs");
+        assert_eq!(entries[&ids[1]]["text"], "Bar");
+        assert!(entries[&ids[1]]["extension"].is_null());
+
+        let res = client
+            .get(&format!("/api/entries/{}", ids[2]))
+            .send()
+            .await?;
+        assert_eq!(res.text().await?, "Baz");
+
+        let ids = vec!["aaaaaa"; MAX_FETCH_IDS + 1];
+        let res = client
+            .post("/api/entries/fetch")
+            .json(&serde_json::json!({ "ids": ids }))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn errors_do_not_leak_content() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            max_lines: Some(1),
+            ..Default::default()
+        })?);
+
+        let entry = Entry {
+            text: "supersecret\nsupersecret".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(!res.text().await?.contains("supersecret"));
+
+        let data = [("text", "supersecret\nsupersecret"), ("expires", "0")];
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        assert!(!res.text().await?.contains("supersecret"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn burn_after_reading() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let payload: RedirectResponse = res.json().await?;
+        assert!(!payload.burn);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            burn_after_reading: Some(true),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let payload: RedirectResponse = res.json().await?;
+        assert!(payload.burn);
+
+        let uri = format!("/api/entries{}", payload.path);
+        let (first, second) = tokio::join!(client.get(&uri).send(), client.get(&uri).send());
+        let mut statuses = [first?.status(), second?.status()];
+        statuses.sort();
+        assert_eq!(statuses, [StatusCode::OK, StatusCode::NOT_FOUND]);
+
+        let res = client.get(&uri).send().await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn password_protected() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "secret".to_string(),
+            burn_after_reading: Some(true),
+            password: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let path: serde_json::Value = res.json().await?;
+        let uri = format!("/api/entries{}", path["path"].as_str().unwrap());
+        let info = format!("{uri}/info");
+
+        let res = client.get(&uri).send().await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = client
+            .get(&uri)
+            .header("x-paste-password", "hunter3")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = client.get(&info).send().await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = client
+            .get(&info)
+            .header("x-paste-password", "hunter2")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = client
+            .get(&uri)
+            .header("x-paste-password", "hunter2")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await?, "secret");
+
+        let res = client
+            .get(&uri)
+            .header("x-paste-password", "hunter2")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn encrypted_paste() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "secret".to_string(),
+            password: Some("hunter2".to_string()),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let uri = format!("/api/entries{}", path["path"].as_str().unwrap());
+
+        let res = client
+            .get(&uri)
+            .header("x-paste-password", "hunter3")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let payload: ErrorPayload = res.json().await?;
+        assert_eq!(payload.message, "unauthorized");
+
+        let res = client
+            .get(&uri)
+            .header("accept", "application/json")
+            .header("x-paste-password", "hunter2")
+            .send()
+            .await?;
+        let metadata: serde_json::Value = res.json().await?;
+        assert_eq!(metadata["text"], "secret");
+
+        let entry = Entry {
+            text: "secret".to_string(),
+            password: Some("hunter2".to_string()),
+            appendable: Some(true),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn compression_threshold() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            compression_level: tower_http::CompressionLevel::Best,
+            compression_min_size: 1024,
+            ..Default::default()
+        })?);
+
+        for (text, compressed) in [("small".to_string(), false), ("large\n".repeat(512), true)] {
+            let entry = Entry {
+                text: text.clone(),
+                ..Default::default()
+            };
+
+            let res = client.post("/api/entries").json(&entry).send().await?;
+            let path: serde_json::Value = res.json().await?;
+            let path = path["path"].as_str().unwrap();
+
+            let res = client
+                .get(&format!("/api/entries{path}"))
+                .header("accept-encoding", "gzip")
+                .send()
+                .await?;
+
+            let encoding = res.headers().get("content-encoding").cloned();
+            let body = res.bytes().await?;
+
+            if compressed {
+                assert_eq!(encoding.unwrap(), "gzip");
+                assert!(body.len() < text.len());
+            } else {
+                assert!(encoding.is_none());
+                assert_eq!(body, text.as_bytes());
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_metadata() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        for (expires, burn_after_reading) in [(Some(3600), None), (None, None), (None, Some(true))]
+        {
+            let entry = Entry {
+                text: "FooBarBaz".to_string(),
+                extension: Some("rs".to_string()),
+                expires,
+                burn_after_reading,
+                ..Default::default()
+            };
+
+            let res = client.post("/api/entries").json(&entry).send().await?;
+            let path: serde_json::Value = res.json().await?;
+            let id = path["path"].as_str().unwrap().trim_end_matches(".rs");
+            let uri = format!("/api/entries{id}");
+
+            let res = client
+                .get(&uri)
+                .header("accept", "application/json")
+                .send()
+                .await?;
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let metadata: serde_json::Value = res.json().await?;
+            assert_eq!(metadata["extension"], "rs");
+            assert!(metadata["seconds_since_creation"].as_u64().unwrap() <= 1);
+
+            match expires {
+                Some(expires) => {
+                    let remaining = metadata["seconds_until_expiry"].as_u64().unwrap();
+                    assert!(remaining <= u64::from(expires) && remaining > 3590);
+                    assert!(metadata["expires"].as_u64().unwrap() >= 3599);
+                }
+                None => {
+                    assert!(metadata["seconds_until_expiry"].is_null());
+                    assert!(metadata["expires"].is_null());
+                }
+            }
+
+            if burn_after_reading.is_some() {
+                assert!(metadata["text"].is_null());
+            } else {
+                assert_eq!(metadata["text"], "FooBarBaz");
+            }
+
+            // Plain requests still return the raw text, burn after reading pastes are still
+            // available.
+            let res = client.get(&uri).send().await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.text().await?, "FooBarBaz");
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn list_themes() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let res = client.get("/api/themes").send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let payload: serde_json::Value = res.json().await?;
+        let themes = payload["themes"].as_array().unwrap();
+        assert!(themes.contains(&"ayu-light".into()));
+        assert!(themes.contains(&"ayu-dark".into()));
+
+        let mut config = Config::default();
+        let custom = config.themes["ayu-dark"].clone();
+        config.themes.insert("custom".to_string(), custom);
+        let client = Client::new(make_app_with_config(config)?);
+
+        let payload: serde_json::Value = client.get("/api/themes").send().await?.json().await?;
+        assert!(payload["themes"]
+            .as_array()
+            .unwrap()
+            .contains(&"custom".into()));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn admin_stats() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+        let res = client.get("/api/admin/stats").send().await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let client = Client::new(make_app_with_config(Config {
+            admin_token: Some("secret".to_string()),
+            ..Default::default()
+        })?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = client.get("/api/admin/stats").send().await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = client
+            .get("/api/admin/stats")
+            .bearer_auth("secret")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let stats: serde_json::Value = res.json().await?;
+        assert_eq!(stats["total"], 1);
+        assert_eq!(stats["last_minute"], 1);
+
+        let res = client.get("/metrics").bearer_auth("secret").send().await?;
+        assert!(res.headers().get("x-api-version").is_none());
+        assert!(res.text().await?.contains("wastebin_inserts_total 1"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn validate_dry_run() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            max_lines: Some(2),
+            admin_token: Some("secret".to_string()),
+            ..Default::default()
+        })?);
+
+        let entry = Entry {
+            text: "Foo\nBar".to_string(),
+            ..Default::default()
+        };
+
+        let res = client
+            .post("/api/entries/validate")
+            .json(&entry)
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload: serde_json::Value = res.json().await?;
+        assert_eq!(payload["ok"], true);
+
+        let entry = Entry {
+            text: "Foo\nBar\nBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client
+            .post("/api/entries/validate")
+            .json(&entry)
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+        let payload: serde_json::Value = res.json().await?;
+        assert_eq!(payload["message"], "paste exceeds the maximum of 2 lines");
+
+        let res = client
+            .get("/api/admin/stats")
+            .bearer_auth("secret")
+            .send()
+            .await?;
+        let stats: serde_json::Value = res.json().await?;
+        assert_eq!(stats["total"], 0);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn concurrent_deletes() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let url = format!("/api/entries{}", path["path"].as_str().unwrap());
+
+        let (first, second) = tokio::join!(client.delete(&url).send(), client.delete(&url).send());
+        assert_eq!(first?.status(), StatusCode::NO_CONTENT);
+        assert_eq!(second?.status(), StatusCode::NO_CONTENT);
+
+        let res = client.get(&url).send().await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let res = client.delete("/api/entries/aaaaaa").send().await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn versioned_and_legacy_paths() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        for prefix in ["/api/v1", "/api"] {
+            let res = client.get(&format!("{prefix}/health")).send().await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers().get("x-api-version").unwrap(), "1");
+
+            let entry = Entry {
+                text: "FooBarBaz".to_string(),
+                ..Default::default()
+            };
+
+            let res = client
+                .post(&format!("{prefix}/entries"))
+                .json(&entry)
+                .send()
+                .await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers().get("x-api-version").unwrap(), "1");
+            let payload: serde_json::Value = res.json().await?;
+            let path = payload["path"].as_str().unwrap();
+
+            for other in ["/api/v1", "/api"] {
+                let res = client.get(&format!("{other}/entries{path}")).send().await?;
+                assert_eq!(res.status(), StatusCode::OK);
+                assert_eq!(res.text().await?, "FooBarBaz");
+
+                let res = client
+                    .get(&format!("{other}/entries{path}/info"))
+                    .send()
+                    .await?;
+                assert_eq!(res.status(), StatusCode::OK);
+            }
+        }
+
+        let res = client.get("/").send().await?;
+        assert!(res.headers().get("x-api-version").is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn delete_secret() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            delete_secret: Some("secret".to_string()),
+            ..Default::default()
+        })?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let url = format!("/api/entries{}", path["path"].as_str().unwrap());
+
+        let res = client
+            .delete(&url)
+            .header("x-delete-secret", "wrong")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(client.get(&url).send().await?.status(), StatusCode::OK);
+
+        let res = client
+            .delete(&url)
+            .header("x-delete-secret", "secret")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert_eq!(
+            client.get(&url).send().await?.status(),
+            StatusCode::NOT_FOUND
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn delete_secret_header() {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-delete-secret", "secret".parse().unwrap());
+
+        assert!(!delete_secret_matches(&Config::default(), &headers).unwrap());
+
+        let config = Config {
+            delete_secret: Some("secret".to_string()),
+            ..Default::default()
+        };
+
+        assert!(delete_secret_matches(&config, &headers).unwrap());
+        assert!(!delete_secret_matches(&config, &HeaderMap::new()).unwrap());
+
+        headers.insert("x-delete-secret", "wrong".parse().unwrap());
+        assert!(matches!(
+            delete_secret_matches(&config, &headers),
+            Err(Error::Unauthorized)
+        ));
+    }
+
+    #[tokio::test]
+    async fn paste_quota() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            max_pastes_per_ip: Some(2),
+            ..Default::default()
+        })?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let mut urls = Vec::new();
+
+        for _ in 0..2 {
+            let res = client.post("/api/entries").json(&entry).send().await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            let path: serde_json::Value = res.json().await?;
+            urls.push(format!("/api/entries{}", path["path"].as_str().unwrap()));
+        }
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        let res = client.delete(&urls[0]).send().await?;
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn append() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "Foo".to_string(),
+            appendable: Some(true),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let payload: serde_json::Value = res.json().await?;
+        let url = format!("/api/entries{}", payload["path"].as_str().unwrap());
+        let token = payload["append_token"].as_str().unwrap();
+
+        let res = client
+            .post(&format!("{url}/append"))
+            .json(&serde_json::json!({ "text": "Bar" }))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let res = client
+            .post(&format!("{url}/append"))
+            .header("x-append-token", token)
+            .json(&serde_json::json!({ "text": "Bar" }))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let payload: serde_json::Value = res.json().await?;
+        assert_eq!(payload["size"], 6);
+
+        let res = client.get(&url).send().await?;
+        assert_eq!(res.text().await?, "FooBar");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn append_exceeding_size_limit() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "a".repeat(4000),
+            appendable: Some(true),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let payload: serde_json::Value = res.json().await?;
+        let url = format!("/api/entries{}", payload["path"].as_str().unwrap());
+        let token = payload["append_token"].as_str().unwrap();
+
+        let res = client
+            .post(&format!("{url}/append"))
+            .header("x-append-token", token)
+            .json(&serde_json::json!({ "text": "b".repeat(100) }))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        let res = client.get(&url).send().await?;
+        assert_eq!(res.text().await?.len(), 4000);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn conditional_raw() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let uri = format!("/api/entries{}", path["path"].as_str().unwrap());
+
+        let res = client.get(&uri).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let etag = res.headers().get("etag").unwrap().clone();
+
+        let res = client
+            .get(&uri)
+            .header("if-none-match", etag)
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            expires: Some(30),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let uri = format!("/api/entries{}", path["path"].as_str().unwrap());
+
+        let res = client.get(&uri).header("if-none-match", "*").send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("cache-control").unwrap(), "no-store");
+        assert_eq!(res.text().await?, "FooBarBaz");
+
+        Ok(())
+    }
 }
@@ -1,4 +1,5 @@
 use crate::cache;
+use crate::config::Config;
 use crate::db::{self, Database};
 use axum::body::HttpBody;
 use axum::BoxError;
@@ -16,6 +17,18 @@ pub(crate) struct Client {
 
 impl Client {
     pub(crate) fn new<S, ResBody>(svc: S) -> Self
+    where
+        S: Service<Request<Body>, Response = http::Response<ResBody>> + Clone + Send + 'static,
+        ResBody: HttpBody + Send + 'static,
+        ResBody::Data: Send,
+        ResBody::Error: Into<BoxError>,
+        S::Future: Send,
+        S::Error: Into<BoxError>,
+    {
+        Self::with_builder(svc, reqwest::Client::builder())
+    }
+
+    pub(crate) fn with_builder<S, ResBody>(svc: S, builder: reqwest::ClientBuilder) -> Self
     where
         S: Service<Request<Body>, Response = http::Response<ResBody>> + Clone + Send + 'static,
         ResBody: HttpBody + Send + 'static,
@@ -32,7 +45,7 @@ impl Client {
             server.await.expect("server error");
         });
 
-        let client = reqwest::Client::builder()
+        let client = builder
             .redirect(reqwest::redirect::Policy::none())
             .build()
             .unwrap();
@@ -40,6 +53,16 @@ impl Client {
         Self { client, addr }
     }
 
+    /// URL of the server, without trailing slash.
+    #[allow(dead_code)]
+    pub(crate) fn base_url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+
+    pub(crate) fn addr(&self) -> SocketAddr {
+        self.addr
+    }
+
     pub(crate) fn get(&self, url: &str) -> RequestBuilder {
         self.client.get(format!("http://{}{}", self.addr, url))
     }
@@ -47,10 +70,27 @@ impl Client {
     pub(crate) fn post(&self, url: &str) -> RequestBuilder {
         self.client.post(format!("http://{}{}", self.addr, url))
     }
+
+    pub(crate) fn delete(&self, url: &str) -> RequestBuilder {
+        self.client.delete(format!("http://{}{}", self.addr, url))
+    }
+
+    pub(crate) fn head(&self, url: &str) -> RequestBuilder {
+        self.client.head(format!("http://{}{}", self.addr, url))
+    }
 }
 
 pub(crate) fn make_app() -> Result<axum::Router, Box<dyn std::error::Error>> {
-    let database = Database::new(db::Open::Memory)?;
-    let cache_layer = cache::Layer::new(database.clone(), 128);
-    Ok(crate::make_app(cache_layer, 4096))
+    make_app_with_config(Config {
+        max_body_size: 4096,
+        ..Default::default()
+    })
+}
+
+pub(crate) fn make_app_with_config(
+    config: Config,
+) -> Result<axum::Router, Box<dyn std::error::Error>> {
+    let database = Database::new(db::Open::Memory)?.with_compression(config.storage_compression());
+    let cache_layer = cache::Layer::new(database, 128);
+    Ok(crate::make_app(cache_layer, config))
 }
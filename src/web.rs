@@ -1,31 +1,47 @@
-use crate::cache::{Key, Layer};
-use crate::highlight::{self, DATA};
-use crate::id::Id;
-use crate::{Entry, Error, Router};
+use crate::base::BaseUrl;
+use crate::cache::{is_valid_extension, Key, Layer};
+use crate::conditional::{self, Caching};
+use crate::config::{About, Config};
+use crate::csp;
+use crate::highlight::{self, LineRange, DATA};
+use crate::id::{Id, Urls};
+use crate::ip::ClientIp;
+use crate::limit::InsertLimiter;
+use crate::metrics::Metrics;
+use crate::password;
+use crate::{deletion_time_remaining, Entry, Error, LogContent, Router};
 use askama::Template;
 use askama_axum::IntoResponse;
-use axum::extract::{Form, Path};
-use axum::headers::HeaderValue;
-use axum::http::{header, StatusCode};
+use axum::extract::{Form, Path, Query};
+use axum::headers::{HeaderMapExt, HeaderValue, IfNoneMatch};
+use axum::http::{header, HeaderMap, StatusCode};
+use axum::response::sse::{Event, KeepAlive, Sse};
 use axum::response::{Redirect, Response};
 use axum::routing::get;
-use axum::{headers, Extension, TypedHeader};
+use axum::{headers, Extension, Json, TypedHeader};
 use bytes::Bytes;
 use once_cell::sync::Lazy;
-use rand::Rng;
 use serde::{Deserialize, Serialize};
 use std::env;
+use std::io::{Cursor, Write};
+use std::sync::Arc;
+use tokio_stream::wrappers::errors::BroadcastStreamRecvError;
+use tokio_stream::wrappers::BroadcastStream;
+use tokio_stream::{Stream, StreamExt};
+use zip::write::FileOptions;
+use zip::{CompressionMethod, ZipWriter};
 
 static TITLE: Lazy<String> =
     Lazy::new(|| env::var("WASTEBIN_TITLE").unwrap_or_else(|_| "wastebin".to_string()));
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Default, Serialize, Deserialize)]
 struct FormEntry {
     text: String,
     extension: Option<String>,
     expires: String,
+    password: Option<String>,
 }
 
 impl From<FormEntry> for Entry {
@@ -43,6 +59,13 @@ impl From<FormEntry> for Entry {
             expires,
             burn_after_reading,
             seconds_since_creation: 0,
+            slug: None,
+            appendable: None,
+            append_token: None,
+            filename: None,
+            client_ip: None,
+            password: entry.password,
+            ciphertext: None,
         }
     }
 }
@@ -51,19 +74,62 @@ impl From<FormEntry> for Entry {
 #[template(path = "index.html")]
 struct Index<'a> {
     title: &'a str,
-    syntaxes: &'a [syntect::parsing::SyntaxReference],
+    syntaxes: Vec<&'a syntect::parsing::SyntaxReference>,
+    max_lines: Option<usize>,
+    default_burn: bool,
+    at_capacity: bool,
     version: &'a str,
+    nonce: String,
+}
+
+#[derive(Template)]
+#[template(path = "about.html")]
+struct AboutPage<'a> {
+    title: &'a str,
+    about: About,
+    max_body_size: usize,
+    max_lines: Option<usize>,
+    max_pastes_per_ip: Option<usize>,
+    version: &'a str,
+    nonce: String,
+}
+
+#[derive(Template)]
+#[template(path = "embed.html")]
+struct Embed<'a> {
+    title: &'a str,
+    formatted: String,
+    version: &'a str,
+    nonce: String,
 }
 
 #[derive(Template)]
 #[template(path = "paste.html")]
 struct Paste<'a> {
     title: &'a str,
-    id: String,
+    urls: Urls,
+    base_path: String,
     formatted: String,
-    extension: String,
     deletion_possible: bool,
+    created: bool,
+    long_lines: bool,
+    /// Raw text and downloads need the password, which the browser cannot send for them.
+    protected: bool,
+    filename: Option<String>,
+    /// First highlighted line, scrolled into view.
+    highlighted_line: Option<usize>,
+    version: &'a str,
+    nonce: String,
+}
+
+#[derive(Template)]
+#[template(path = "unlock.html")]
+struct UnlockPage<'a> {
+    title: &'a str,
+    action: String,
+    wrong: bool,
     version: &'a str,
+    nonce: String,
 }
 
 #[derive(Template)]
@@ -71,7 +137,9 @@ struct Paste<'a> {
 struct BurnPage<'a> {
     title: &'a str,
     id: String,
+    base_path: String,
     version: &'a str,
+    nonce: String,
 }
 
 #[derive(Template)]
@@ -80,113 +148,424 @@ struct ErrorPage<'a> {
     title: &'a str,
     error: String,
     version: &'a str,
+    nonce: String,
+}
+
+/// Compact JSON representation served for the `.json` suffix.
+#[derive(Serialize)]
+struct PasteJson {
+    id: String,
+    extension: Option<String>,
+    text: String,
+}
+
+/// Text appended to a tailed paste.
+#[derive(Serialize)]
+struct TailEvent {
+    text: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+struct ShowParams {
+    #[serde(default)]
+    created: bool,
+    /// Lines to highlight, e.g. `12-18`. Malformed values are ignored.
+    hl: Option<String>,
+}
+
+/// Password submitted to read a protected paste.
+#[derive(Deserialize)]
+struct UnlockForm {
+    password: String,
 }
 
 type ErrorHtml<'a> = (StatusCode, ErrorPage<'a>);
 
 impl From<Error> for ErrorHtml<'_> {
     fn from(err: Error) -> Self {
+        let (status, error) = err.into_response_parts();
+
         let html = ErrorPage {
             title: &TITLE,
-            error: err.to_string(),
+            error,
             version: VERSION,
+            nonce: csp::nonce(),
         };
 
-        (err.into(), html)
+        (status, html)
     }
 }
 
-#[allow(clippy::unused_async)]
-async fn index<'a>() -> Index<'a> {
-    Index {
+async fn index<'a>(
+    layer: Extension<Layer>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Index<'a>, ErrorHtml<'a>> {
+    let at_capacity = layer.at_capacity(&config).await?;
+
+    // Only offer syntaxes whose extension would be accepted on insert.
+    Ok(Index {
         title: &TITLE,
-        syntaxes: DATA.syntax_set.syntaxes(),
+        syntaxes: DATA
+            .syntax_set
+            .syntaxes()
+            .iter()
+            .filter(|syntax| {
+                syntax
+                    .file_extensions
+                    .first()
+                    .is_some_and(|ext| is_valid_extension(ext))
+            })
+            .collect(),
+        max_lines: config.max_lines,
+        default_burn: config.default_burn,
+        at_capacity,
         version: VERSION,
-    }
+        nonce: csp::nonce(),
+    })
 }
 
 async fn insert(
     Form(entry): Form<FormEntry>,
     layer: Extension<Layer>,
-) -> Result<Redirect, ErrorHtml<'static>> {
-    let id: Id = tokio::task::spawn_blocking(|| {
-        let mut rng = rand::thread_rng();
-        rng.gen::<u32>()
-    })
-    .await
-    .map_err(Error::from)?
-    .into();
+    Extension(config): Extension<Arc<Config>>,
+    metrics: Extension<Metrics>,
+    limiter: Extension<InsertLimiter>,
+    ClientIp(ip): ClientIp,
+    BaseUrl(base): BaseUrl,
+) -> Result<Response, ErrorHtml<'static>> {
+    let mut entry: Entry = entry.into();
+    entry.normalize(&config);
+    entry.validate(&config)?;
+
+    tracing::debug!(%ip, content = %LogContent::new(&entry.text, &config), "inserting paste");
+
+    limiter.check(ip)?;
+    let quota = Layer::claim_quota(&mut entry, ip, &config);
 
-    let entry: Entry = entry.into();
-    let url = id.to_url_path(&entry);
     let burn_after_reading = entry.burn_after_reading.unwrap_or(false);
 
-    layer.insert(id, entry).await?;
+    password::protect(&mut entry).await?;
+    let id = layer.insert_random(entry.clone(), quota).await?;
+    let extension = entry.extension.as_deref();
+    metrics.record_insert();
 
-    if burn_after_reading {
-        Ok(Redirect::to(&format!("/burn{url}")))
+    let location = if burn_after_reading {
+        config.burn_location(&base, &id.page("", extension))
+    } else if config.created_banner {
+        format!("{}?created=true", id.page(&base, extension))
     } else {
-        Ok(Redirect::to(&url))
-    }
+        id.page(&base, extension)
+    };
+
+    Ok((
+        config.redirect_status,
+        [(header::LOCATION, location.as_str())],
+    )
+        .into_response())
+}
+
+/// Return `true` if the client asked for HTML, as browsers do.
+fn accepts_html(headers: &HeaderMap) -> bool {
+    headers
+        .get_all(header::ACCEPT)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .any(|value| value.contains("text/html"))
 }
 
 async fn show(
     id_with_opt_ext: Path<String>,
+    Query(params): Query<ShowParams>,
+    headers: HeaderMap,
+    layer: Extension<Layer>,
+    Extension(config): Extension<Arc<Config>>,
+    BaseUrl(base): BaseUrl,
+) -> Result<Response, ErrorHtml<'static>> {
+    show_paste(
+        id_with_opt_ext,
+        params,
+        &headers,
+        &layer,
+        &config,
+        &base,
+        None,
+    )
+    .await
+}
+
+/// Show a password protected paste with the password submitted from the unlock page.
+async fn unlock(
+    id_with_opt_ext: Path<String>,
+    Query(params): Query<ShowParams>,
+    headers: HeaderMap,
     layer: Extension<Layer>,
-) -> Result<Paste<'static>, ErrorHtml<'static>> {
+    Extension(config): Extension<Arc<Config>>,
+    BaseUrl(base): BaseUrl,
+    Form(form): Form<UnlockForm>,
+) -> Result<Response, ErrorHtml<'static>> {
+    let password = Some(form.password).filter(|password| !password.is_empty());
+    show_paste(
+        id_with_opt_ext,
+        params,
+        &headers,
+        &layer,
+        &config,
+        &base,
+        password,
+    )
+    .await
+}
+
+/// Render paste `id_with_opt_ext` or, if it is protected and `password` does not match, a page
+/// asking for the password. The text is decrypted and highlighted on the server.
+async fn show_paste(
+    Path(id_with_opt_ext): Path<String>,
+    params: ShowParams,
+    headers: &HeaderMap,
+    layer: &Layer,
+    config: &Config,
+    base: &str,
+    password: Option<String>,
+) -> Result<Response, ErrorHtml<'static>> {
     let title = &TITLE;
-    let key = Key::try_from(id_with_opt_ext)?;
-    let id = key.id();
-    let extension = key.extension();
-    let entry = layer.get_formatted(key).await?;
+    let key = Key::try_from(Path(id_with_opt_ext))?;
+    let action = key.raw_id().page(base, key.ext());
+
+    if key.extension() == "json" && !accepts_html(headers) {
+        let entry = layer.get(key.raw_id(), password).await?;
+
+        return Ok(Json(PasteJson {
+            id: key.id(),
+            extension: entry.extension,
+            text: entry.text,
+        })
+        .into_response());
+    }
+
+    if config.auto_extension && key.ext().is_none() {
+        if let Some(ext) = layer.peek(key.raw_id()).await?.extension {
+            let location = key.raw_id().page(base, Some(&ext));
+            return Ok(Redirect::temporary(&location).into_response());
+        }
+    }
 
-    Ok(Paste {
+    let id = key.raw_id();
+    let wrong = password.is_some();
+
+    let entry = match layer.get_formatted(key, password, config).await {
+        Err(Error::Unauthorized) => {
+            let page = UnlockPage {
+                title,
+                action,
+                wrong,
+                version: VERSION,
+                nonce: csp::nonce(),
+            };
+
+            return Ok((StatusCode::UNAUTHORIZED, page).into_response());
+        }
+        result => result?,
+    };
+
+    // Link the extension the paste was highlighted with, also if it was detected.
+    let extension = entry.extension.as_deref().unwrap_or("txt");
+    let urls = id.urls(base, extension);
+    // Raw text and downloads of protected pastes need the password, so they are not advertised.
+    let links = if entry.protected {
+        HeaderMap::new()
+    } else {
+        id.links(base, extension)
+    };
+
+    let deletion_possible =
+        deletion_time_remaining(entry.seconds_since_creation, config.deletion_window).is_some();
+
+    let lines = params
+        .hl
+        .as_deref()
+        .and_then(|hl| hl.parse::<LineRange>().ok());
+    let formatted = match lines {
+        Some(range) => highlight::mark_lines(&entry.formatted, range),
+        None => entry.formatted,
+    };
+    let highlighted_line = lines
+        .map(|range| range.start)
+        .filter(|start| formatted.contains(&format!(r#"id="L{start}""#)));
+
+    // The page changes with the delete link and the banner, so both are part of the tag.
+    let caching = Caching::paste(
+        entry.cacheable,
+        &(
+            &urls.page,
+            &formatted,
+            deletion_possible,
+            params.created,
+            entry.long_lines,
+            &entry.filename,
+        ),
+        entry.seconds_since_creation,
+        entry.appendable,
+    );
+
+    if caching.not_modified(&headers.typed_get::<IfNoneMatch>().map(TypedHeader)) {
+        return Ok((StatusCode::NOT_MODIFIED, links, caching.headers()).into_response());
+    }
+
+    let paste = Paste {
         title,
-        id,
-        extension,
-        formatted: entry.formatted,
-        deletion_possible: entry.seconds_since_creation < 60,
+        urls,
+        base_path: config.base_path.clone(),
+        formatted,
+        deletion_possible,
+        created: params.created,
+        long_lines: entry.long_lines,
+        protected: entry.protected,
+        filename: entry.filename,
+        highlighted_line,
+        version: VERSION,
+        nonce: csp::nonce(),
+    };
+
+    Ok((links, caching.headers(), paste).into_response())
+}
+
+/// Describe the instance and its limits, only available if configured.
+#[allow(clippy::unused_async)]
+async fn about<'a>(
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<AboutPage<'a>, ErrorHtml<'a>> {
+    let about = config.about.clone().ok_or(Error::NotFound)?;
+
+    Ok(AboutPage {
+        title: &TITLE,
+        about,
+        max_body_size: config.max_body_size,
+        max_lines: config.max_lines,
+        max_pastes_per_ip: config.max_pastes_per_ip,
         version: VERSION,
+        nonce: csp::nonce(),
     })
 }
 
+/// Minimal page with just the highlighted paste, meant to be embedded in an iframe by the origins
+/// allowed in the config.
+async fn embed(
+    id_with_opt_ext: Path<String>,
+    layer: Extension<Layer>,
+    Extension(config): Extension<Arc<Config>>,
+) -> Result<Response, ErrorHtml<'static>> {
+    let key = Key::try_from(id_with_opt_ext)?;
+    let entry = layer.get_formatted(key, None, &config).await?;
+
+    let page = Embed {
+        title: &TITLE,
+        formatted: entry.formatted,
+        version: VERSION,
+        nonce: csp::nonce(),
+    };
+
+    let mut headers = HeaderMap::new();
+
+    if let Ok(value) = HeaderValue::from_str(&config.frame_ancestors.directive()) {
+        headers.insert(header::CONTENT_SECURITY_POLICY, value);
+    }
+
+    if let Some(value) = config.frame_ancestors.x_frame_options() {
+        headers.insert(header::X_FRAME_OPTIONS, HeaderValue::from_static(value));
+    }
+
+    Ok((headers, page).into_response())
+}
+
+/// Stream text appended to an appendable paste as server-sent events. Subscribers that fall
+/// behind skip the missed chunks and receive a `lagged` event with their number instead.
+async fn events(
+    Path(id): Path<String>,
+    layer: Extension<Layer>,
+) -> Result<Sse<impl Stream<Item = Result<Event, serde_json::Error>>>, ErrorHtml<'static>> {
+    let id = Id::try_from(id.as_str())?;
+    let receiver = layer.subscribe(id).await?;
+
+    let stream = BroadcastStream::new(receiver).map(|message| match message {
+        Ok(text) => Event::default().json_data(TailEvent { text }),
+        Err(BroadcastStreamRecvError::Lagged(skipped)) => {
+            Ok(Event::default().event("lagged").data(skipped.to_string()))
+        }
+    });
+
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
 #[allow(clippy::unused_async)]
-async fn burn_link(Path(id): Path<String>) -> BurnPage<'static> {
+async fn burn_link(Path(id): Path<String>, BaseUrl(base): BaseUrl) -> BurnPage<'static> {
     BurnPage {
         title: &TITLE,
         id,
+        base_path: base,
         version: VERSION,
+        nonce: csp::nonce(),
     }
 }
 
 async fn delete(
     Path(id): Path<String>,
     layer: Extension<Layer>,
+    Extension(config): Extension<Arc<Config>>,
+    BaseUrl(base): BaseUrl,
 ) -> Result<Redirect, ErrorHtml<'static>> {
     let id = Id::try_from(id.as_str())?;
-    let entry = layer.get(id).await?;
-
-    if entry.seconds_since_creation > 60 {
-        Err(Error::DeletionTimeExpired)?
-    }
+    layer
+        .delete(id, config.delete_missing_ok, Some(config.deletion_window))
+        .await?;
 
-    layer.delete(id).await?;
+    Ok(Redirect::to(&format!("{base}/")))
+}
 
-    Ok(Redirect::to("/"))
+/// Content type of a download with `extension`, plain text for unknown extensions.
+fn content_type(extension: &str) -> &'static str {
+    match extension {
+        "csv" => "text/csv; charset=utf-8",
+        "json" => "application/json",
+        "md" | "markdown" => "text/markdown; charset=utf-8",
+        "svg" => "image/svg+xml",
+        "xml" => "application/xml",
+        _ => "text/plain; charset=utf-8",
+    }
 }
 
 async fn download(
     Path((id, extension)): Path<(String, String)>,
     layer: Extension<Layer>,
+    Extension(config): Extension<Arc<Config>>,
 ) -> Result<Response<String>, ErrorHtml<'static>> {
     // Validate extension.
     if !extension.is_ascii() {
         Err(Error::IllegalCharacters)?
     }
 
-    let raw_string = layer.get(Id::try_from(id.as_str())?).await?.text;
-    let content_type = "text; charset=utf-8";
-    let content_disposition = format!(r#"attachment; filename="{id}.{extension}"#);
+    if id.len() + extension.len() + 1 > config.max_filename_length {
+        Err(Error::FilenameTooLong(config.max_filename_length))?
+    }
+
+    let extension = match &config.download_extensions {
+        Some(allowed) if !allowed.contains(&extension) => {
+            if config.download_fallback {
+                "txt".to_string()
+            } else {
+                Err(Error::ExtensionNotAllowed)?
+            }
+        }
+        _ => extension,
+    };
+
+    let entry = layer.get(Id::try_from(id.as_str())?, None).await?;
+    let raw_string = entry.text;
+    let content_type = content_type(&extension);
+    let filename = entry
+        .filename
+        .unwrap_or_else(|| format!("{id}.{extension}"));
+    let disposition = config.download_disposition(&extension, content_type);
+    let content_disposition = format!(r#"{disposition}; filename="{filename}""#);
 
     Ok(Response::builder()
         .header(header::CONTENT_TYPE, HeaderValue::from_static(content_type))
@@ -195,32 +574,97 @@ async fn download(
         .map_err(Error::from)?)
 }
 
-#[allow(clippy::unused_async)]
-async fn favicon() -> impl IntoResponse {
-    (
-        TypedHeader(headers::ContentType::png()),
-        Bytes::from_static(include_bytes!("../assets/favicon.png")),
+/// Zip archive containing `text` as a single file called `name`.
+fn archive(name: &str, text: &str) -> Result<Vec<u8>, zip::result::ZipError> {
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+    let mut writer = ZipWriter::new(Cursor::new(Vec::new()));
+
+    writer.start_file(name, options)?;
+    writer.write_all(text.as_bytes())?;
+
+    Ok(writer.finish()?.into_inner())
+}
+
+/// Download the paste as zip archive named after its stored file name or extension.
+async fn download_archive(
+    Path(id): Path<String>,
+    layer: Extension<Layer>,
+) -> Result<Response, ErrorHtml<'static>> {
+    let entry = layer.get(Id::try_from(id.as_str())?, None).await?;
+
+    let name = entry.filename.unwrap_or_else(|| {
+        let extension = entry.extension.as_deref().unwrap_or("txt");
+        format!("{id}.{extension}")
+    });
+
+    let text = entry.text;
+    let data = tokio::task::spawn_blocking(move || archive(&name, &text))
+        .await
+        .map_err(Error::from)?
+        .map_err(Error::from)?;
+
+    let content_disposition = format!(r#"attachment; filename="{id}.zip""#);
+
+    Ok((
+        [
+            (header::CONTENT_TYPE, "application/zip"),
+            (header::CONTENT_DISPOSITION, content_disposition.as_str()),
+        ],
+        data,
     )
+        .into_response())
+}
+
+#[allow(clippy::unused_async)]
+async fn favicon(
+    Extension(config): Extension<Arc<Config>>,
+    if_none_match: Option<TypedHeader<IfNoneMatch>>,
+) -> Response {
+    let (content_type, data) = match &config.favicon {
+        Some(favicon) => (
+            headers::ContentType::from(favicon.content_type.clone()),
+            favicon.data.clone(),
+        ),
+        None => (
+            headers::ContentType::png(),
+            Bytes::from_static(include_bytes!("../assets/favicon.png")),
+        ),
+    };
+
+    let etag = conditional::etag(&data);
+    let headers = (TypedHeader(content_type), TypedHeader(etag.clone()));
+
+    if conditional::not_modified(&if_none_match, &etag) {
+        return (StatusCode::NOT_MODIFIED, headers).into_response();
+    }
+
+    (headers, data).into_response()
 }
 
 pub fn routes() -> Router {
     Router::new()
         .route("/", get(index).post(insert))
-        .route("/:id", get(show))
+        .route("/about", get(about))
+        .route("/:id", get(show).post(unlock))
+        .route("/:id/embed", get(embed))
+        .route("/:id/events", get(events))
+        .route("/:id/archive.zip", get(download_archive))
         .route("/burn/:id", get(burn_link))
         .route("/delete/:id", get(delete))
         .route("/download/:id/:extension", get(download))
         .route("/favicon.png", get(favicon))
-        .route("/style.css", get(|| async { highlight::main() }))
-        .route("/dark.css", get(|| async { highlight::dark() }))
-        .route("/light.css", get(|| async { highlight::light() }))
+        .route("/style.css", get(highlight::main))
+        .route("/dark.css", get(highlight::dark))
+        .route("/light.css", get(highlight::light))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::test_helpers::{make_app, Client};
+    use crate::config::{Favicon, FrameAncestors};
+    use crate::test_helpers::{make_app, make_app_with_config, Client};
     use http::StatusCode;
+    use std::collections::HashSet;
 
     #[tokio::test]
     async fn unknown_paste() -> Result<(), Box<dyn std::error::Error>> {
@@ -232,6 +676,31 @@ mod tests {
         Ok(())
     }
 
+    #[tokio::test]
+    async fn http2_prior_knowledge() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::with_builder(
+            make_app()?,
+            reqwest::Client::builder().http2_prior_knowledge(),
+        );
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.version(), http::Version::HTTP_2);
+
+        let location = res.headers().get("location").unwrap().to_str()?;
+        let res = client.get(location).send().await?;
+        assert_eq!(res.version(), http::Version::HTTP_2);
+        assert!(res.text().await?.contains("FooBarBaz"));
+
+        Ok(())
+    }
+
     #[tokio::test]
     async fn insert() -> Result<(), Box<dyn std::error::Error>> {
         let client = Client::new(make_app()?);
@@ -240,6 +709,7 @@ mod tests {
             text: "FooBarBaz".to_string(),
             extension: None,
             expires: "0".to_string(),
+            ..Default::default()
         };
 
         let res = client.post("/").form(&data).send().await?;
@@ -264,6 +734,7 @@ mod tests {
             text: "FooBarBaz".to_string(),
             extension: None,
             expires: "0".to_string(),
+            ..Default::default()
         };
 
         let res = client.post("/").form(&data).send().await?;
@@ -278,4 +749,1070 @@ mod tests {
 
         Ok(())
     }
+
+    #[tokio::test]
+    async fn size_limit_counts_bytes() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            max_body_size: 4096,
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "é".repeat(2048),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+        let data = FormEntry {
+            text: "é".repeat(2049),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.status(), StatusCode::PAYLOAD_TOO_LARGE);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn configured_redirect_with_banner() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            redirect_status: StatusCode::FOUND,
+            created_banner: true,
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.status(), StatusCode::FOUND);
+
+        let location = res.headers().get("location").unwrap().to_str()?;
+        assert!(location.ends_with("?created=true"));
+
+        let content = client.get(location).send().await?.text().await?;
+        assert!(content.contains(r#"class="banner""#));
+
+        let path = location.trim_end_matches("?created=true");
+        let content = client.get(path).send().await?.text().await?;
+        assert!(!content.contains(r#"class="banner""#));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn max_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            max_lines: Some(3),
+            ..Default::default()
+        })?);
+
+        let content = client.get("/").send().await?.text().await?;
+        assert!(content.contains("at most 3 lines"));
+
+        let data = FormEntry {
+            text: "1\n2\n3".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+        let data = FormEntry {
+            text: "1\n2\n3\n4".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn default_burn() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+        let content = client.get("/").send().await?.text().await?;
+        assert!(content.contains(r#"selected="" value="">never"#));
+        assert!(!content.contains(r#"selected="" value="burn""#));
+
+        let client = Client::new(make_app_with_config(Config {
+            default_burn: true,
+            ..Default::default()
+        })?);
+
+        let content = client.get("/").send().await?.text().await?;
+        assert!(!content.contains(r#"selected="" value="">never"#));
+        assert!(content.contains(r#"selected="" value="burn""#));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn at_capacity() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            max_pastes: Some(1),
+            ..Default::default()
+        })?);
+
+        let content = client.get("/").send().await?.text().await?;
+        assert!(!content.contains("disabled"));
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+
+        let content = client.get("/").send().await?.text().await?;
+        assert!(content.contains(r#"<button type="submit" title="Paste" disabled="">"#));
+        assert!(content.contains("no new pastes can be created"));
+
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.status(), StatusCode::SERVICE_UNAVAILABLE);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn custom_svg_favicon() -> Result<(), Box<dyn std::error::Error>> {
+        let svg = br#"<svg xmlns="http://www.w3.org/2000/svg"></svg>"#;
+
+        let client = Client::new(make_app_with_config(Config {
+            favicon: Some(Favicon::new(
+                std::path::Path::new("favicon.svg"),
+                svg.to_vec(),
+            )),
+            ..Default::default()
+        })?);
+
+        let res = client.get("/favicon.png").send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-type").unwrap(), "image/svg+xml");
+        assert_eq!(res.bytes().await?.as_ref(), svg);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn link_header() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: Some("rs".to_string()),
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        let id = location.trim_start_matches('/').trim_end_matches(".rs");
+
+        let res = client.get(location).send().await?;
+        let links = res.headers().get("link").unwrap().to_str()?;
+        assert!(links.contains(&format!(r#"</{id}.rs>; rel="canonical""#)));
+        assert!(links.contains(&format!(r#"</api/entries/{id}>; rel="alternate""#)));
+        assert!(links.contains(&format!(r#"</download/{id}/rs>; rel="enclosure""#)));
+
+        let res = client.get(&format!("/api/entries/{id}")).send().await?;
+        let links = res.headers().get("link").unwrap().to_str()?;
+        assert!(links.contains(&format!(r#"</download/{id}/txt>; rel="enclosure""#)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn json_suffix() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: Some("rs".to_string()),
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        let id = location.trim_start_matches('/').trim_end_matches(".rs");
+
+        let res = client.get(&format!("/{id}.json")).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let paste: serde_json::Value = res.json().await?;
+        assert_eq!(paste["id"], id);
+        assert_eq!(paste["extension"], "rs");
+        assert_eq!(paste["text"], "FooBarBaz");
+
+        let res = client
+            .get(&format!("/{id}.json"))
+            .header("accept", "text/html")
+            .send()
+            .await?;
+        assert!(res.text().await?.contains("<html>"));
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: None,
+            expires: "burn".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        let id = location.trim_start_matches("/burn/");
+
+        let res = client.get(&format!("/{id}.json")).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = client.get(&format!("/{id}.json")).send().await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_allowlist() -> Result<(), Box<dyn std::error::Error>> {
+        for fallback in [false, true] {
+            let client = Client::new(make_app_with_config(Config {
+                download_extensions: Some(["rs".to_string(), "txt".to_string()].into()),
+                download_fallback: fallback,
+                ..Default::default()
+            })?);
+
+            let data = FormEntry {
+                text: "FooBarBaz".to_string(),
+                extension: None,
+                expires: "0".to_string(),
+                ..Default::default()
+            };
+
+            let res = client.post("/").form(&data).send().await?;
+            let location = res.headers().get("location").unwrap().to_str()?;
+
+            let res = client
+                .get(&format!("/download{location}/rs"))
+                .send()
+                .await?;
+            assert_eq!(res.status(), StatusCode::OK);
+
+            let res = client
+                .get(&format!("/download{location}/exe"))
+                .send()
+                .await?;
+
+            if fallback {
+                assert_eq!(res.status(), StatusCode::OK);
+                let disposition = res.headers().get("content-disposition").unwrap();
+                assert!(disposition.to_str()?.contains(".txt"));
+            } else {
+                assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn tail_events() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "Foo".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let payload: serde_json::Value = res.json().await?;
+        let path = payload["path"].as_str().unwrap();
+
+        let res = client.get(&format!("{path}/events")).send().await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let entry = Entry {
+            appendable: Some(true),
+            ..entry
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let payload: serde_json::Value = res.json().await?;
+        let path = payload["path"].as_str().unwrap();
+        let token = payload["append_token"].as_str().unwrap();
+
+        let mut events = client.get(&format!("{path}/events")).send().await?;
+        assert_eq!(events.status(), StatusCode::OK);
+        assert_eq!(
+            events.headers().get("content-type").unwrap(),
+            "text/event-stream"
+        );
+
+        let res = client
+            .post(&format!("/api/entries{path}/append"))
+            .header("x-append-token", token)
+            .json(&serde_json::json!({ "text": "Bar\n" }))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let chunk = events.chunk().await?.unwrap();
+        assert!(String::from_utf8_lossy(&chunk).contains(r#"data:{"text":"Bar\n"}"#));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn burn_redirect_with_base_path() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            base_path: "/paste".to_string(),
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: None,
+            expires: "burn".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        assert!(location.starts_with("/paste/burn/"));
+
+        let id = location.trim_start_matches("/paste/burn/");
+        let content = client
+            .get(location.trim_start_matches("/paste"))
+            .send()
+            .await?
+            .text()
+            .await?;
+        assert!(content.contains(&format!(r#"href="/paste/{id}""#)));
+
+        let data = FormEntry {
+            expires: "0".to_string(),
+            ..data
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        assert!(location.starts_with("/paste/"));
+        assert!(!location.starts_with("/paste/burn/"));
+
+        let client = Client::new(make_app_with_config(Config {
+            burn_redirect: Some("/landing?paste={path}".to_string()),
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            expires: "burn".to_string(),
+            ..data
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        assert!(location.starts_with("/landing?paste=/"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn stored_filename() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            extension: Some("txt".to_string()),
+            filename: Some("../logs/\"build\".log".to_string()),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let path = path["path"].as_str().unwrap();
+        let id = path.trim_start_matches('/').trim_end_matches(".txt");
+
+        let content = client.get(path).send().await?.text().await?;
+        assert!(content.contains("_build_.log"));
+
+        let res = client.get(&format!("/download/{id}/txt")).send().await?;
+        let disposition = res.headers().get("content-disposition").unwrap();
+        assert!(disposition.to_str()?.contains(r#"filename="_build_.log"#));
+
+        let entry = Entry {
+            filename: None,
+            ..entry
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let id = path["path"].as_str().unwrap().trim_start_matches('/');
+        let id = id.trim_end_matches(".txt");
+
+        let res = client.get(&format!("/download/{id}/txt")).send().await?;
+        let disposition = res.headers().get("content-disposition").unwrap();
+        assert!(disposition
+            .to_str()?
+            .contains(&format!(r#"filename="{id}.txt"#)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn about_page() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+        let res = client.get("/about").send().await?;
+        assert_eq!(res.status(), StatusCode::NOT_FOUND);
+
+        let client = Client::new(make_app_with_config(Config {
+            about: Some(About {
+                description: Some("A bin for testing".to_string()),
+                contact: Some("admin@example.com".to_string()),
+                retention: None,
+            }),
+            max_lines: Some(42),
+            ..Default::default()
+        })?);
+
+        let res = client.get("/about").send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let content = res.text().await?;
+        assert!(content.contains("A bin for testing"));
+        assert!(content.contains("admin@example.com"));
+        assert!(content.contains("at most 42 lines per paste"));
+        assert!(!content.contains("Retention"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn embed() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            frame_ancestors: FrameAncestors::Sources(vec!["https://example.com".to_string()]),
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "fn main() {}".to_string(),
+            extension: Some("rs".to_string()),
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+
+        let res = client.get(&format!("{location}/embed")).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("content-security-policy").unwrap(),
+            "frame-ancestors https://example.com"
+        );
+        assert!(res.headers().get("x-frame-options").is_none());
+
+        let content = res.text().await?;
+        assert!(content.contains(r#"<pre class="code">"#));
+        assert!(content.contains("main"));
+        assert!(!content.contains("paste-box"));
+        assert!(!content.contains("<script"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_disposition() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            inline_downloads: HashSet::from(["txt".to_string(), "csv".to_string()]),
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "<svg></svg>".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+
+        for (extension, disposition) in [
+            ("txt", "inline"),
+            ("csv", "inline"),
+            ("rs", "attachment"),
+            ("svg", "attachment"),
+        ] {
+            let res = client
+                .get(&format!("/download{location}/{extension}"))
+                .send()
+                .await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(
+                res.headers().get("content-type").unwrap(),
+                content_type(extension)
+            );
+
+            let header = res.headers().get("content-disposition").unwrap().to_str()?;
+            assert!(header.starts_with(&format!("{disposition};")), "{header}");
+            assert!(
+                header.ends_with(&format!(r#"filename="{}.{extension}""#, &location[1..])),
+                "{header}"
+            );
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn download_disposition_uses_served_extension() -> Result<(), Box<dyn std::error::Error>>
+    {
+        let client = Client::new(make_app_with_config(Config {
+            inline_downloads: HashSet::from(["txt".to_string()]),
+            ..Default::default()
+        })?);
+
+        let entry = Entry {
+            text: "<svg></svg>".to_string(),
+            filename: Some("notes.txt".to_string()),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        let id = path["path"].as_str().unwrap().trim_start_matches('/');
+
+        let res = client.get(&format!("/download/{id}/svg")).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("content-type").unwrap(), "image/svg+xml");
+
+        let header = res.headers().get("content-disposition").unwrap().to_str()?;
+        assert_eq!(header, r#"attachment; filename="notes.txt""#);
+
+        let res = client.get(&format!("/download/{id}/txt")).send().await?;
+        let header = res.headers().get("content-disposition").unwrap().to_str()?;
+        assert!(header.starts_with("inline;"), "{header}");
+
+        Ok(())
+    }
+
+    #[test]
+    fn download_content_types() {
+        assert_eq!(content_type("json"), "application/json");
+        assert_eq!(content_type("svg"), "image/svg+xml");
+        assert_eq!(content_type("md"), "text/markdown; charset=utf-8");
+        assert_eq!(content_type("csv"), "text/csv; charset=utf-8");
+        assert_eq!(content_type("rs"), "text/plain; charset=utf-8");
+        assert_eq!(content_type("txt"), "text/plain; charset=utf-8");
+    }
+
+    #[tokio::test]
+    async fn auto_extension() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            auto_extension: true,
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "#!/usr/bin/env python3\nprint('hello')".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        assert!(location.ends_with(".py"));
+
+        let id = location.trim_start_matches('/').trim_end_matches(".py");
+        let res = client.get(&format!("/{id}")).send().await?;
+        assert_eq!(res.status(), StatusCode::TEMPORARY_REDIRECT);
+        assert_eq!(res.headers().get("location").unwrap(), location);
+
+        let data = FormEntry {
+            text: "#!/usr/bin/env python3\nprint('hello')".to_string(),
+            extension: Some("txt".to_string()),
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        assert!(location.ends_with(".txt"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn urls_with_base_path() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            base_path: "/paste".to_string(),
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: Some("rs".to_string()),
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        let path = location.trim_start_matches("/paste");
+        let id = path.trim_start_matches('/').trim_end_matches(".rs");
+
+        let res = client.get(path).send().await?;
+        let links = res.headers().get("link").unwrap().to_str()?;
+        assert!(links.contains(&format!(r#"</paste/api/entries/{id}>; rel="alternate""#)));
+
+        let content = res.text().await?;
+        assert!(content.contains(&format!(r#"data-raw-url="/paste/api/entries/{id}""#)));
+        assert!(content.contains(&format!(r#"href="/paste/api/entries/{id}">raw</a>"#)));
+        assert!(content.contains(&format!(r#"href="/paste/download/{id}/rs">"#)));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn urls_with_forwarded_host() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            base_path: "/paste".to_string(),
+            forwarded_host: true,
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: Some("rs".to_string()),
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client
+            .post("/")
+            .header("x-forwarded-host", "bin.example.com")
+            .header("x-forwarded-proto", "https")
+            .form(&data)
+            .send()
+            .await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        let path = location.trim_start_matches("https://bin.example.com/paste");
+        assert_ne!(path, location);
+        let id = path.trim_start_matches('/').trim_end_matches(".rs");
+
+        let res = client
+            .get(path)
+            .header("x-forwarded-host", "bin.example.com")
+            .header("x-forwarded-proto", "https")
+            .send()
+            .await?;
+        let content = res.text().await?;
+        assert!(content.contains(&format!(
+            r#"data-raw-url="https://bin.example.com/paste/api/entries/{id}""#
+        )));
+        assert!(content.contains(&format!(
+            r#"href="https://bin.example.com/paste/download/{id}/rs">"#
+        )));
+
+        let res = client.get(path).send().await?;
+        let content = res.text().await?;
+        assert!(content.contains(&format!(r#"data-raw-url="/paste/api/entries/{id}""#)));
+
+        let res = client.get(&format!("/delete/{id}")).send().await?;
+        assert_eq!(res.headers().get("location").unwrap(), "/paste/");
+
+        let entry = Entry {
+            text: "FooBarBaz".to_string(),
+            extension: Some("rs".to_string()),
+            ..Default::default()
+        };
+
+        let res = client.post("/api/entries").json(&entry).send().await?;
+        let path: serde_json::Value = res.json().await?;
+        assert!(path["path"].as_str().unwrap().starts_with("/paste/"));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn embed_framing_denied_by_default() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let data = FormEntry {
+            text: "fn main() {}".to_string(),
+            extension: Some("rs".to_string()),
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+
+        let res = client.get(&format!("{location}/embed")).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("content-security-policy").unwrap(),
+            "frame-ancestors 'none'"
+        );
+        assert_eq!(res.headers().get("x-frame-options").unwrap(), "DENY");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn zip_archive() -> Result<(), Box<dyn std::error::Error>> {
+        use std::io::Read;
+
+        let client = Client::new(make_app()?);
+
+        let data = FormEntry {
+            text: "fn main() {}".to_string(),
+            extension: Some("rs".to_string()),
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        let id = location.trim_start_matches('/').trim_end_matches(".rs");
+
+        let res = client.get(&format!("/{id}/archive.zip")).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(
+            res.headers().get("content-type").unwrap(),
+            "application/zip"
+        );
+
+        let mut archive = zip::ZipArchive::new(Cursor::new(res.bytes().await?))?;
+        assert_eq!(archive.len(), 1);
+
+        let mut file = archive.by_index(0)?;
+        assert_eq!(file.name(), format!("{id}.rs"));
+
+        let mut content = String::new();
+        file.read_to_string(&mut content)?;
+        assert_eq!(content, "fn main() {}");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn overlong_download_filename() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app_with_config(Config {
+            max_filename_length: 16,
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+
+        let res = client
+            .get(&format!("/download{location}/rs"))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = client
+            .get(&format!("/download{location}/{}", "a".repeat(10)))
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::BAD_REQUEST);
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn csp_nonce() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+        let res = client.get("/").send().await?;
+        assert!(res.headers().get("content-security-policy").is_none());
+
+        let client = Client::new(make_app_with_config(Config {
+            content_security_policy: true,
+            ..Default::default()
+        })?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+
+        let res = client.get(location).send().await?;
+        let policy = res
+            .headers()
+            .get("content-security-policy")
+            .unwrap()
+            .to_str()?
+            .to_string();
+
+        let nonce = policy
+            .split("'nonce-")
+            .nth(1)
+            .and_then(|rest| rest.split('\'').next())
+            .unwrap();
+
+        assert!(!nonce.is_empty());
+        assert!(policy.contains(&format!("style-src 'self' 'nonce-{nonce}'")));
+        assert!(policy.contains(&format!("script-src 'nonce-{nonce}'")));
+
+        let content = res.text().await?;
+        assert!(content.contains(&format!(r#"<style nonce="{nonce}">"#)));
+        assert!(content.contains(&format!(r#"<script nonce="{nonce}">"#)));
+
+        let res = client.get("/style.css").send().await?;
+        assert!(res.headers().get("content-security-policy").is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn head_index_and_assets() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let res = client.head("/").send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res
+            .headers()
+            .get("content-type")
+            .unwrap()
+            .to_str()?
+            .starts_with("text/html"));
+        assert!(res.bytes().await?.is_empty());
+
+        for path in ["/style.css", "/dark.css", "/light.css"] {
+            let res = client.head(path).send().await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert_eq!(res.headers().get("content-type").unwrap(), "text/css");
+            assert_eq!(res.headers().get("cache-control").unwrap(), "max-age=3600");
+            assert!(res.bytes().await?.is_empty());
+        }
+
+        let res = client.head("/favicon.png").send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.bytes().await?.is_empty());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn conditional_assets() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        for path in ["/style.css", "/dark.css", "/light.css", "/favicon.png"] {
+            let res = client.get(path).send().await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            let etag = res.headers().get("etag").unwrap().clone();
+
+            let res = client
+                .get(path)
+                .header("if-none-match", etag.clone())
+                .send()
+                .await?;
+            assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+            assert_eq!(res.headers().get("etag").unwrap(), etag);
+            assert!(res.bytes().await?.is_empty());
+
+            let res = client
+                .get(path)
+                .header("if-none-match", r#""outdated""#)
+                .send()
+                .await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            assert!(!res.bytes().await?.is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn password_protected() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: Some("rs".to_string()),
+            expires: "0".to_string(),
+            password: Some("hunter2".to_string()),
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        assert_eq!(res.status(), StatusCode::SEE_OTHER);
+        let location = res.headers().get("location").unwrap().to_str()?.to_string();
+        let id = location.trim_start_matches('/').trim_end_matches(".rs");
+
+        let res = client.get(&location).send().await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let content = res.text().await?;
+        assert!(content.contains(r#"type="password""#));
+        assert!(!content.contains("wrong password"));
+
+        let res = client
+            .post(&location)
+            .form(&[("password", "hunter3")])
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert!(res.text().await?.contains("wrong password"));
+
+        let res = client
+            .post(&location)
+            .form(&[("password", "hunter2")])
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert!(res.headers().get("link").is_none());
+        let content = res.text().await?;
+        assert!(content.contains("FooBarBaz"));
+        assert!(!content.contains(&format!("/api/entries/{id}")));
+        assert!(!content.contains(&format!("/download/{id}")));
+
+        let res = client
+            .get(&format!("/api/entries/{id}"))
+            .header("x-paste-password", "hunter2")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.text().await?, "FooBarBaz");
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn conditional_paste() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?.to_string();
+
+        let res = client.get(&location).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("cache-control").unwrap(), "no-cache");
+        assert!(res.headers().get("last-modified").is_some());
+        let etag = res.headers().get("etag").unwrap().clone();
+
+        let res = client
+            .get(&location)
+            .header("if-none-match", etag)
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert!(res.bytes().await?.is_empty());
+
+        let data = FormEntry {
+            text: "FooBarBaz".to_string(),
+            extension: None,
+            expires: "burn".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?;
+        let id = location.trim_start_matches("/burn/");
+
+        let res = client
+            .get(&format!("/{id}"))
+            .header("if-none-match", "*")
+            .send()
+            .await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers().get("cache-control").unwrap(), "no-store");
+        assert!(res.headers().get("etag").is_none());
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn detected_syntax_links() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let data = FormEntry {
+            text: "[1, 2, 3]".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?.to_string();
+        let id = location.trim_start_matches('/');
+
+        let res = client.get(&location).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let links = res.headers().get("link").unwrap().to_str()?.to_string();
+        assert!(links.contains(&format!("</download/{id}/json>")), "{links}");
+        assert!(res.text().await?.contains(&format!("/download/{id}/json")));
+
+        Ok(())
+    }
+
+    #[tokio::test]
+    async fn highlighted_lines() -> Result<(), Box<dyn std::error::Error>> {
+        let client = Client::new(make_app()?);
+
+        let data = FormEntry {
+            text: "foo\nbar\nbaz".to_string(),
+            extension: None,
+            expires: "0".to_string(),
+            ..Default::default()
+        };
+
+        let res = client.post("/").form(&data).send().await?;
+        let location = res.headers().get("location").unwrap().to_str()?.to_string();
+
+        let res = client.get(&format!("{location}?hl=2-3")).send().await?;
+        assert_eq!(res.status(), StatusCode::OK);
+        let content = res.text().await?;
+        assert!(content.contains(r#"<tr id="L2" class="highlighted">"#));
+        assert!(content.contains(r#"<tr id="L3" class="highlighted">"#));
+        assert!(content.contains(r#"<tr id="L1">"#));
+        assert!(content.contains("getElementById('L2')"));
+
+        for hl in ["abc", "9999-10000", "3-1"] {
+            let res = client.get(&format!("{location}?hl={hl}")).send().await?;
+            assert_eq!(res.status(), StatusCode::OK);
+            let content = res.text().await?;
+            assert!(content.contains("bar"));
+            assert!(!content.contains("highlighted"), "{hl}");
+            assert!(!content.contains("scrollIntoView"), "{hl}");
+        }
+
+        Ok(())
+    }
 }